@@ -110,7 +110,7 @@ export default function () {
 
       const success = check(res, {
         'status is 200': (r) => r.status === 200,
-        'is array': (r) => Array.isArray(r.json()),
+        'has items array': (r) => Array.isArray(r.json('items')),
         'response time < 300ms': (r) => r.timings.duration < 300,
       });
 
@@ -145,7 +145,7 @@ export default function () {
       const res = http.get(`${BASE_URL}/api/users/nonexistent-user-${Math.random()}`);
 
       const success = check(res, {
-        'status is 500': (r) => r.status === 500,  // Our app returns 500 for not found
+        'status is 404': (r) => r.status === 404,
         'response time < 200ms': (r) => r.timings.duration < 200,
       });
 
@@ -210,7 +210,7 @@ export default function () {
       );
 
       const success = check(res, {
-        'status is 500': (r) => r.status === 500,
+        'status is 400': (r) => r.status === 400,
         'response time < 500ms': (r) => r.timings.duration < 500,
       });
 
@@ -225,7 +225,7 @@ export default function () {
     group('Invalid Fibonacci Parameter', () => {
       const res = http.post(
         `${BASE_URL}/api/compute`,
-        JSON.stringify({ n: 100 }),  // n > 50 should fail
+        JSON.stringify({ n: 100 }),  // fib(100) overflows u64 and should fail
         {
           headers: {
             'Content-Type': 'application/json',
@@ -234,7 +234,7 @@ export default function () {
       );
 
       const success = check(res, {
-        'status is 500': (r) => r.status === 500,
+        'status is 400': (r) => r.status === 400,
         'response time < 500ms': (r) => r.timings.duration < 500,
       });
 
@@ -0,0 +1,85 @@
+/// Bearer-token checks shared by the admin and metrics endpoints
+///
+/// Handlers taking `AdminAuth` as their first argument reject callers before any other
+/// extractor runs, so unauthenticated requests never get their body parsed.
+use std::future::{Ready, ready};
+
+use actix_web::{
+    FromRequest, HttpRequest, HttpResponse,
+    dev::Payload,
+    error::InternalError,
+    http::header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    web,
+};
+use tracing::warn;
+
+/// Bearer token guarding the `/admin` routes (`ADMIN_TOKEN`)
+#[derive(Debug)]
+pub struct AdminToken(pub String);
+
+/// Proof that the request carries the `AdminToken` as its bearer token
+///
+/// Extraction fails with a 401 when the token is missing or wrong, or when no `AdminToken` is
+/// registered as app data.
+#[derive(Debug)]
+pub struct AdminAuth;
+
+impl FromRequest for AdminAuth {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let authorized = req
+            .app_data::<web::Data<AdminToken>>()
+            .is_some_and(|token| has_bearer_token(req, &token.0));
+
+        if authorized {
+            return ready(Ok(Self));
+        }
+
+        warn!(path = req.path(), "Rejected unauthorized admin request");
+        ready(Err(InternalError::from_response(
+            "unauthorized",
+            unauthorized(),
+        )
+        .into()))
+    }
+}
+
+/// Compare secrets without short-circuiting on the first mismatching byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Whether the request carries `Authorization: Bearer <expected>`
+pub fn has_bearer_token(req: &HttpRequest, expected: &str) -> bool {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), expected.as_bytes()))
+}
+
+/// 401 asking the client for a bearer token
+pub fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, "Bearer"))
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_time_eq_compares_whole_secrets() {
+        assert!(constant_time_eq(b"s3cret", b"s3cret"));
+        assert!(!constant_time_eq(b"s3cret", b"s3creT"));
+        assert!(!constant_time_eq(b"s3cret", b"s3cret-longer"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
@@ -0,0 +1,329 @@
+/// Application configuration loaded from environment variables
+///
+/// Every setting has a sensible default so the tutorial runs with no configuration,
+/// while deployments (e.g. containers) can override values as needed.
+use std::{
+    fmt,
+    num::{NonZeroU32, NonZeroUsize},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use actix_web::http::{Uri, header::HeaderName};
+
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_DRAIN_DELAY_SECS: u64 = 0;
+const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_SLOW_REQUEST_MS: u64 = 1_000;
+const DEFAULT_RATE_LIMIT_BURST: u32 = 20;
+const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+const DEFAULT_IDEMPOTENCY_TTL_SECS: u64 = 600;
+const DEFAULT_REQUEST_ID_HEADER: &str = "x-request-id";
+/// `APP_ENV` value in which debug endpoints stay unmounted unless explicitly enabled
+const PRODUCTION_ENVIRONMENT: &str = "production";
+/// actix-web's own default for pending connections
+const DEFAULT_BACKLOG: u32 = 2048;
+
+/// HTTP server configuration (`APP_HOST`, `APP_PORT`, `APP_SHUTDOWN_TIMEOUT_SECS`,
+/// `APP_REQUEST_TIMEOUT_MS`, `RATE_LIMIT_RPS`, `RATE_LIMIT_BURST`, `MAX_BODY_BYTES`,
+/// `IDEMPOTENCY_TTL_SECS`, `REQUEST_ID_HEADER`, `CORS_ALLOWED_ORIGINS`, `APP_WORKERS`,
+/// `APP_BACKLOG`, `APP_DRAIN_DELAY_SECS`, `METRICS_LOG_INTERVAL_SECS`, `DEBUG_ENDPOINTS`,
+/// `SLOW_REQUEST_MS`, `ADMIN_TOKEN`)
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Worker threads, each running its own event loop; defaults to the available CPUs
+    pub workers: usize,
+    /// Connections queued by the OS before new ones are refused
+    pub backlog: u32,
+    /// How long a graceful shutdown waits for in-flight requests
+    pub shutdown_timeout: Duration,
+    /// How long new requests get a 503 before the listener closes, so load balancers can
+    /// notice the failing readiness probe
+    pub drain_delay: Duration,
+    /// Maximum time a single request may take before a 503 is returned
+    pub request_timeout: Duration,
+    /// Requests slower than this log a `slow_request` warning
+    pub slow_request_threshold: Duration,
+    /// Per-client requests per second; rate limiting is disabled when unset
+    pub rate_limit_rps: Option<f64>,
+    /// Requests a client may burst above the steady rate
+    pub rate_limit_burst: u32,
+    /// Largest JSON request body accepted before responding 413
+    pub max_body_bytes: usize,
+    /// How long an `Idempotency-Key` keeps returning the original response
+    pub idempotency_ttl: Duration,
+    /// Header carrying the correlation ID in requests and responses
+    pub request_id_header: HeaderName,
+    /// Origins allowed to call the API from browsers; none unless configured
+    pub cors_allowed_origins: Vec<String>,
+    /// How often key counters are logged as a summary event; disabled unless configured
+    pub metrics_log_interval: Option<Duration>,
+    /// Whether `/api/debug/*` routes are mounted; off by default when `APP_ENV=production`
+    pub debug_endpoints: bool,
+    /// Bearer token required by `/admin/*`; the admin routes are unmounted unless set
+    pub admin_token: Option<String>,
+}
+
+/// Error returned when an environment variable holds an unusable value
+#[derive(Debug)]
+pub struct ConfigError {
+    pub var: &'static str,
+    pub value: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid value {:?} for {}: {}",
+            self.value, self.var, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ServerConfig {
+    /// Read the bind address from `APP_HOST` / `APP_PORT`, falling back to `127.0.0.1:8080`,
+    /// the graceful shutdown timeout from `APP_SHUTDOWN_TIMEOUT_SECS` (default 30s), and the
+    /// per-request timeout from `APP_REQUEST_TIMEOUT_MS` (default 30s)
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let host = env_string("APP_HOST").unwrap_or_else(|| DEFAULT_HOST.to_string());
+        let port = env_parse("APP_PORT")?.unwrap_or(DEFAULT_PORT);
+
+        // Zero would leave the server without workers or queue, so parse as non-zero types
+        let workers = env_parse::<NonZeroUsize>("APP_WORKERS")?
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get);
+        let backlog =
+            env_parse::<NonZeroU32>("APP_BACKLOG")?.map_or(DEFAULT_BACKLOG, NonZeroU32::get);
+        let shutdown_timeout = Duration::from_secs(
+            env_parse("APP_SHUTDOWN_TIMEOUT_SECS")?.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+        );
+
+        let drain_delay = Duration::from_secs(
+            env_parse("APP_DRAIN_DELAY_SECS")?.unwrap_or(DEFAULT_DRAIN_DELAY_SECS),
+        );
+
+        let request_timeout = Duration::from_millis(
+            env_parse("APP_REQUEST_TIMEOUT_MS")?.unwrap_or(DEFAULT_REQUEST_TIMEOUT_MS),
+        );
+
+        let slow_request_threshold =
+            Duration::from_millis(env_parse("SLOW_REQUEST_MS")?.unwrap_or(DEFAULT_SLOW_REQUEST_MS));
+
+        let rate_limit_rps = env_parse::<f64>("RATE_LIMIT_RPS")?;
+        if let Some(rps) = rate_limit_rps.filter(|rps| !rps.is_finite() || *rps <= 0.0) {
+            return Err(ConfigError {
+                var: "RATE_LIMIT_RPS",
+                value: rps.to_string(),
+                reason: "must be a positive number".to_string(),
+            });
+        }
+        let rate_limit_burst = env_parse("RATE_LIMIT_BURST")?.unwrap_or(DEFAULT_RATE_LIMIT_BURST);
+        let max_body_bytes = env_parse("MAX_BODY_BYTES")?.unwrap_or(DEFAULT_MAX_BODY_BYTES);
+        let idempotency_ttl = Duration::from_secs(
+            env_parse("IDEMPOTENCY_TTL_SECS")?.unwrap_or(DEFAULT_IDEMPOTENCY_TTL_SECS),
+        );
+
+        let request_id_header = env_parse("REQUEST_ID_HEADER")?
+            .unwrap_or_else(|| HeaderName::from_static(DEFAULT_REQUEST_ID_HEADER));
+
+        let cors_allowed_origins = env_string("CORS_ALLOWED_ORIGINS")
+            .map(|raw| parse_origins(&raw))
+            .transpose()?
+            .unwrap_or_default();
+
+        // Zero disables the summary like leaving it unset, rather than logging in a tight loop
+        let metrics_log_interval = env_parse::<u64>("METRICS_LOG_INTERVAL_SECS")?
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
+
+        let debug_endpoints = env_parse("DEBUG_ENDPOINTS")?
+            .unwrap_or_else(|| env_string("APP_ENV").as_deref() != Some(PRODUCTION_ENVIRONMENT));
+
+        let admin_token = env_string("ADMIN_TOKEN");
+
+        Ok(Self {
+            host,
+            port,
+            workers,
+            backlog,
+            shutdown_timeout,
+            drain_delay,
+            request_timeout,
+            slow_request_threshold,
+            rate_limit_rps,
+            rate_limit_burst,
+            max_body_bytes,
+            idempotency_ttl,
+            request_id_header,
+            cors_allowed_origins,
+            metrics_log_interval,
+            debug_endpoints,
+            admin_token,
+        })
+    }
+
+    /// Address string suitable for `HttpServer::bind`
+    pub fn bind_address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// Parse comma-separated origins such as `https://app.example.com`
+///
+/// Each entry needs a scheme and host; `*` is rejected so credentials are never opened to
+/// every site by accident.
+fn parse_origins(raw: &str) -> Result<Vec<String>, ConfigError> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            let is_origin = origin
+                .parse::<Uri>()
+                .is_ok_and(|uri| uri.scheme().is_some() && uri.host().is_some());
+
+            if is_origin {
+                Ok(origin.trim_end_matches('/').to_string())
+            } else {
+                Err(ConfigError {
+                    var: "CORS_ALLOWED_ORIGINS",
+                    value: origin.to_string(),
+                    reason: "expected an origin like https://app.example.com".to_string(),
+                })
+            }
+        })
+        .collect()
+}
+
+/// Read an environment variable, treating unset and blank values alike
+pub fn env_string(var: &str) -> Option<String> {
+    std::env::var(var)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Parse an optional environment variable, reporting values that fail to parse
+pub fn env_parse<T>(var: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    parse_value(var, env_string(var))
+}
+
+/// Parse the raw value of `var`, if any; `None` means unset so the caller's default applies
+fn parse_value<T>(var: &'static str, raw: Option<String>) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    raw.map(|raw| {
+        raw.parse::<T>().map_err(|err| ConfigError {
+            var,
+            reason: err.to_string(),
+            value: raw,
+        })
+    })
+    .transpose()
+}
+
+/// Parse an optional environment variable, recording invalid values and using `default`
+pub fn env_parse_or<T>(var: &'static str, default: T, errors: &mut Vec<ConfigError>) -> T
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    parse_or(var, env_string(var), default, errors)
+}
+
+/// `parse_value`, recording an invalid value and using `default` instead
+fn parse_or<T>(
+    var: &'static str,
+    raw: Option<String>,
+    default: T,
+    errors: &mut Vec<ConfigError>,
+) -> T
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    parse_value(var, raw)
+        .unwrap_or_else(|err| {
+            errors.push(err);
+            None
+        })
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_parses_or_falls_back() {
+        let port = parse_value::<u16>("APP_PORT", Some("9090".to_string())).unwrap();
+        assert_eq!(port, Some(9090));
+
+        let unset = parse_value::<u16>("APP_PORT", None).unwrap();
+        assert_eq!(unset.unwrap_or(DEFAULT_PORT), 8080);
+    }
+
+    #[test]
+    fn invalid_port_is_reported() {
+        for raw in ["http", "70000", "-1"] {
+            let err = parse_value::<u16>("APP_PORT", Some(raw.to_string())).unwrap_err();
+            assert_eq!(err.var, "APP_PORT");
+            assert_eq!(err.value, raw);
+        }
+    }
+
+    #[test]
+    fn workers_and_backlog_must_be_positive() {
+        let workers = parse_value::<NonZeroUsize>("APP_WORKERS", Some("4".to_string())).unwrap();
+        assert_eq!(workers.map(NonZeroUsize::get), Some(4));
+        let backlog = parse_value::<NonZeroU32>("APP_BACKLOG", Some("1024".to_string())).unwrap();
+        assert_eq!(backlog.map(NonZeroU32::get), Some(1024));
+
+        for raw in ["0", "-2", "many"] {
+            let err =
+                parse_value::<NonZeroUsize>("APP_WORKERS", Some(raw.to_string())).unwrap_err();
+            assert_eq!(err.var, "APP_WORKERS");
+            assert_eq!(err.value, raw);
+
+            let err = parse_value::<NonZeroU32>("APP_BACKLOG", Some(raw.to_string())).unwrap_err();
+            assert_eq!(err.var, "APP_BACKLOG");
+        }
+    }
+
+    #[test]
+    fn invalid_value_falls_back_to_default() {
+        let mut errors = Vec::new();
+
+        let level = parse_or(
+            "LOG_STDOUT",
+            Some("sometimes".to_string()),
+            true,
+            &mut errors,
+        );
+        assert!(level);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].var, "LOG_STDOUT");
+
+        assert!(!parse_or(
+            "LOG_STDOUT",
+            Some("false".to_string()),
+            true,
+            &mut errors
+        ));
+        assert_eq!(errors.len(), 1);
+    }
+}
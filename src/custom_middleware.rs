@@ -5,21 +5,447 @@
 /// - Create spans for HTTP requests
 /// - Track request/response metrics
 /// - Link logs across the entire request lifecycle
-use crate::metrics;
+/// - Bound how long a request may run
+/// - Rate limit clients with a token bucket
+/// - Turn handler panics into traced 500s
+/// - Redact credentials before headers reach logs
+/// - Tag every request span with deployment-wide attributes
+/// - Record response compression on the request span
+/// - Allow configured browser origins (CORS)
+/// - Carry caller deadlines (`X-Request-Deadline`) to handlers
+/// - Turn away new requests with a 503 while shutting down
+/// - Attach the start of the request body to spans of failed requests
+use crate::{
+    deadline::{Deadline, REQUEST_DEADLINE_HEADER},
+    error::{ApiError, ErrorKind, RequestId},
+    metrics, propagation,
+};
+use actix_cors::Cors;
 use actix_web::{
-    Error, HttpMessage,
-    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
-    http::StatusCode,
+    Error, HttpMessage, HttpResponse, ResponseError,
+    body::{BodySize, BoxBody, MessageBody},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    error::InternalError,
+    http::{
+        ConnectionType, Method, StatusCode,
+        header::{
+            ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, HeaderMap,
+            HeaderName, HeaderValue, ORIGIN, REFERER, RETRY_AFTER, USER_AGENT, X_FORWARDED_FOR,
+        },
+    },
+    web::Bytes,
+};
+use futures::{FutureExt, StreamExt, future::LocalBoxFuture};
+use once_cell::sync::Lazy;
+use std::{
+    any::Any,
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    rc::Rc,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
 };
-use futures::future::LocalBoxFuture;
-use std::rc::Rc;
-use tracing::{info, warn};
+use tracing::{debug, error, info, warn};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use uuid::Uuid;
 
+/// Upper bound on accepted inbound request IDs to keep log lines and span attributes small
+const MAX_REQUEST_ID_LEN: usize = 128;
+
 /// Middleware that injects a request ID and creates a span for each request
-pub struct RequestIdMiddleware;
+///
+/// The ID is read from and echoed in `header` (`REQUEST_ID_HEADER`, default `X-Request-ID`).
+/// Requests taking longer than `slow_request_threshold` (`SLOW_REQUEST_MS`) log a
+/// `slow_request` warning.
+pub struct RequestIdMiddleware {
+    header: HeaderName,
+    slow_request_threshold: Duration,
+    span_attributes: Arc<[(String, String)]>,
+    capture_error_bodies: bool,
+}
+
+impl RequestIdMiddleware {
+    pub fn new(header: HeaderName, slow_request_threshold: Duration) -> Self {
+        Self {
+            header,
+            slow_request_threshold,
+            span_attributes: Arc::from([]),
+            capture_error_bodies: false,
+        }
+    }
+
+    /// Add fixed attributes (see `global_span_attributes`) to every request span
+    pub fn with_span_attributes(mut self, span_attributes: Arc<[(String, String)]>) -> Self {
+        self.span_attributes = span_attributes;
+        self
+    }
+
+    /// Record the start of the request body on failed requests (see `capture_error_bodies`)
+    pub fn with_error_body_capture(mut self, enabled: bool) -> Self {
+        self.capture_error_bodies = enabled;
+        self
+    }
+}
+
+/// Reuse an upstream request ID header when it is sane, otherwise mint a new UUID
+fn resolve_request_id(req: &ServiceRequest, header: &HeaderName) -> String {
+    req.headers()
+        .get(header)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Echo the request ID to the client unless the handler already set the header
+fn insert_request_id_header(headers: &mut HeaderMap, header: &HeaderName, request_id: &str) {
+    if headers.contains_key(header) {
+        return;
+    }
+
+    if let Ok(value) = HeaderValue::from_str(request_id) {
+        headers.insert(header.clone(), value);
+    }
+}
+
+/// Originating client address: the leftmost `X-Forwarded-For` hop, else the socket peer
+///
+/// Only the first hop is taken; later entries are proxies appended along the way.
+fn client_address(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get(X_FORWARDED_FOR)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::to_string)
+        .or_else(|| req.peer_addr().map(|addr| addr.ip().to_string()))
+}
+
+/// Header value as a string, omitting missing, non-UTF-8 or blank values
+fn header_str(req: &ServiceRequest, name: HeaderName) -> Option<&str> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// Declared request body size from `Content-Length`, if present and valid
+fn request_content_length(req: &ServiceRequest) -> Option<u64> {
+    req.headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Response body size when known up front; streaming bodies report `None`
+fn response_body_size<B: MessageBody>(res: &ServiceResponse<B>) -> Option<u64> {
+    match res.response().body().size() {
+        BodySize::Sized(size) => Some(size),
+        BodySize::None | BodySize::Stream => None,
+    }
+}
+
+/// Mark the span's OpenTelemetry status as `Error` so trace UIs flag the request
+fn record_span_error(span: &tracing::Span, message: &str) {
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_message", message);
+}
+
+/// Set the span status for a response, however it was produced
+///
+/// Per the HTTP semantic conventions for server spans: 5xx is `Error`, 4xx is the client's
+/// mistake and stays unset, anything else is `Ok`.
+fn record_response_status(
+    span: &tracing::Span,
+    status: StatusCode,
+    message: impl FnOnce() -> String,
+) {
+    if status.is_server_error() {
+        record_span_error(span, &message());
+    } else if !status.is_client_error() {
+        span.record("otel.status_code", "OK");
+    }
+}
+
+/// Emit the structured `http.access` event for a finished request
+///
+/// Replaces actix's text `Logger` so access logs stay queryable as JSON in Loki, e.g.
+/// `{job="rust-app"} | json | target="http.access"`.
+fn log_access(method: &str, path: &str, status: u16, duration_ms: u64, request_id: &str) {
+    info!(
+        name: "http.access",
+        target: "http.access",
+        method,
+        path,
+        status,
+        duration_ms,
+        request_id,
+        "{method} {path} {status} {duration_ms}ms"
+    );
+}
+
+/// Status recorded for requests abandoned before a response, following nginx's convention
+const CLIENT_CLOSED_REQUEST: u16 = 499;
+
+/// Records a request as cancelled if its future is dropped before a response is produced
+///
+/// actix drops the service future when the client disconnects mid-request; without this such
+/// requests would be missing from `http_requests_total` and the latency histogram. Disarmed
+/// once a response (or error) exists, so every request is observed exactly once.
+struct CancellationGuard {
+    method: String,
+    endpoint: String,
+    start_time: Instant,
+    span: tracing::Span,
+    armed: bool,
+}
+
+impl CancellationGuard {
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for CancellationGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+
+        let elapsed = self.start_time.elapsed();
+        let duration_ms = elapsed.as_millis() as u64;
+        self.span.record("status", CLIENT_CLOSED_REQUEST);
+        self.span.record("duration_ms", duration_ms);
+        record_span_error(&self.span, "request cancelled before a response was sent");
+
+        metrics::track_request_result(
+            &self.method,
+            &self.endpoint,
+            CLIENT_CLOSED_REQUEST,
+            elapsed,
+            propagation::trace_id(&self.span).as_deref(),
+        );
+
+        self.span.in_scope(|| {
+            warn!(duration_ms, "Request cancelled before a response was sent");
+        });
+    }
+}
+
+/// Emit a `slow_request` warning when a request took longer than `threshold`
+fn log_if_slow(threshold: Duration, elapsed: Duration, method: &str, endpoint: &str, path: &str) {
+    if elapsed <= threshold {
+        return;
+    }
+
+    let duration_ms = elapsed.as_millis() as u64;
+    warn!(
+        name: "slow_request",
+        method,
+        endpoint,
+        path,
+        duration_ms,
+        threshold_ms = threshold.as_millis() as u64,
+        "Slow request: {method} {endpoint} took {duration_ms}ms"
+    );
+}
+
+/// Leading request-body bytes kept for error spans
+const MAX_CAPTURED_BODY_BYTES: usize = 1024;
+
+/// Whether failed requests record the start of their body (`CAPTURE_ERROR_BODIES`, default false)
+///
+/// Bodies can contain personal data, so this is meant for debugging sessions only.
+pub fn capture_error_bodies() -> bool {
+    let mut errors = Vec::new();
+    let enabled = crate::config::env_parse_or("CAPTURE_ERROR_BODIES", false, &mut errors);
+    for err in errors {
+        warn!("Ignoring invalid middleware setting: {err}");
+    }
+    enabled
+}
+
+/// First `MAX_CAPTURED_BODY_BYTES` of the request body, filled as the handler reads it
+#[derive(Default)]
+struct CapturedBody {
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+impl CapturedBody {
+    fn push(&mut self, chunk: &[u8]) {
+        let room = MAX_CAPTURED_BODY_BYTES - self.bytes.len();
+        self.bytes
+            .extend_from_slice(&chunk[..chunk.len().min(room)]);
+        self.truncated |= chunk.len() > room;
+    }
+}
+
+/// Tee the request payload so the bytes the handler reads are also copied, up to the limit
+///
+/// The body still streams through unbuffered; only the bounded prefix is kept.
+fn capture_request_body(req: &mut ServiceRequest) -> Rc<RefCell<CapturedBody>> {
+    let captured = Rc::new(RefCell::new(CapturedBody::default()));
+    let sink = captured.clone();
+
+    let payload = req.take_payload().inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+            sink.borrow_mut().push(chunk);
+        }
+    });
+    req.set_payload(Payload::from(payload.boxed_local()));
+
+    captured
+}
+
+/// Record the captured body on the span, but only for failed (4xx/5xx) requests
+fn record_captured_body(
+    span: &tracing::Span,
+    captured: Option<&Rc<RefCell<CapturedBody>>>,
+    status: StatusCode,
+) {
+    let Some(captured) = captured else {
+        return;
+    };
+    if !status.is_client_error() && !status.is_server_error() {
+        return;
+    }
+
+    let captured = captured.borrow();
+    span.record(
+        "http.request.body",
+        String::from_utf8_lossy(&captured.bytes).as_ref(),
+    );
+    span.record("http.request.body.truncated", captured.truncated);
+}
+
+/// Headers whose values never reach logs or spans
+const DEFAULT_SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "set-cookie",
+    "x-api-key",
+];
+
+/// Placeholder logged in place of a sensitive header's value
+const REDACTED: &str = "[REDACTED]";
+
+/// Denylist: the defaults plus any names in `SENSITIVE_HEADERS` (comma-separated)
+///
+/// Names are lowercased; `HeaderName` is always lowercase, so matching is case-insensitive.
+static SENSITIVE_HEADERS: Lazy<HashSet<String>> = Lazy::new(|| {
+    let extra = crate::config::env_string("SENSITIVE_HEADERS").unwrap_or_default();
+
+    DEFAULT_SENSITIVE_HEADERS
+        .iter()
+        .map(|name| name.to_string())
+        .chain(
+            extra
+                .split(',')
+                .map(|name| name.trim().to_ascii_lowercase())
+                .filter(|name| !name.is_empty()),
+        )
+        .collect()
+});
+
+/// Header names and values safe to log, with denylisted values replaced by `[REDACTED]`
+///
+/// Repeated headers are joined with `, `; non-UTF-8 values are logged lossily.
+fn redacted_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    let mut redacted = BTreeMap::<String, String>::new();
+
+    for (name, value) in headers {
+        let value = if SENSITIVE_HEADERS.contains(name.as_str()) {
+            REDACTED.into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+
+        redacted
+            .entry(name.as_str().to_string())
+            .and_modify(|joined| {
+                joined.push_str(", ");
+                joined.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+
+    redacted
+}
+
+/// Attributes from `SPAN_GLOBAL_ATTRS` (e.g. `region=eu-west-1,cluster=prod`) to add to every
+/// request span; malformed pairs are skipped with a warning
+pub fn global_span_attributes() -> Vec<(String, String)> {
+    let Some(raw) = crate::config::env_string("SPAN_GLOBAL_ATTRS") else {
+        return Vec::new();
+    };
+
+    raw.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .filter_map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Some((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => {
+                warn!("Ignoring malformed SPAN_GLOBAL_ATTRS entry {pair:?}, expected key=value");
+                None
+            }
+        })
+        .collect()
+}
+
+/// CORS policy admitting `allowed_origins`, exposing the correlation headers to browser code
+///
+/// Preflight `OPTIONS` requests are answered here without reaching a handler; they still pass
+/// through `RequestIdMiddleware`, so metrics count them under `method="OTHER"`.
+pub fn cors(allowed_origins: &[String], request_id_header: &HeaderName) -> Cors {
+    allowed_origins.iter().fold(
+        Cors::default()
+            .allowed_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+            .allow_any_header()
+            .expose_headers([
+                request_id_header.clone(),
+                HeaderName::from_static("traceparent"),
+            ])
+            .max_age(3600),
+        |cors, origin| cors.allowed_origin(origin),
+    )
+}
+
+/// Best-effort text of a panic payload (`panic!` produces `&str` or `String`)
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string())
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
 
+/// Collapse a request path into a low-cardinality metric label
+///
+/// - Any query string (`?...`) or fragment (`#...`) is stripped, so full URIs are accepted
+/// - Empty segments are dropped: duplicate slashes collapse and a trailing slash is ignored,
+///   so `/api/users/`, `//api//users` and `/api/users` all map to `/api/users`
+/// - Segments are percent-decoded (`/api/users/%31%32` is `/api/users/12`); those with a
+///   malformed escape or bytes that aren't UTF-8 become `{invalid}`
+/// - ID-like segments become `{id}`
 fn normalize_endpoint(path: &str) -> String {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
     let mut parts = Vec::new();
 
     for segment in path.split('/') {
@@ -27,10 +453,10 @@ fn normalize_endpoint(path: &str) -> String {
             continue;
         }
 
-        if segment.parse::<u64>().is_ok() || Uuid::parse_str(segment).is_ok() {
-            parts.push("{id}");
-        } else {
-            parts.push(segment);
+        match decode_segment(segment) {
+            None => parts.push(Cow::Borrowed(INVALID_SEGMENT)),
+            Some(segment) if is_id_segment(&segment) => parts.push(Cow::Borrowed("{id}")),
+            Some(segment) => parts.push(segment),
         }
     }
 
@@ -41,13 +467,109 @@ fn normalize_endpoint(path: &str) -> String {
     }
 }
 
+/// Placeholder for path segments that can't be decoded
+const INVALID_SEGMENT: &str = "{invalid}";
+
+/// Percent-decode one path segment; `None` for a malformed escape or non-UTF-8 bytes
+fn decode_segment(segment: &str) -> Option<Cow<'_, str>> {
+    if !segment.contains('%') {
+        return Some(Cow::Borrowed(segment));
+    }
+
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            // Two ASCII hex digits, so both conversions succeed
+            let hex = std::str::from_utf8(hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok().map(Cow::Owned)
+}
+
+/// Metric label for requests that don't correspond to a registered route
+const OTHER_ENDPOINT: &str = "other";
+
+/// Bounded metric label for a request method
+///
+/// Methods outside the ones the API routes use (including `HEAD`, `OPTIONS` and extension
+/// methods) collapse to `OTHER`, so clients can't grow the label set with arbitrary tokens.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::PATCH => "PATCH",
+        _ => "OTHER",
+    }
+}
+
+/// Method part of a request span name
+///
+/// The HTTP semantic conventions know the RFC 9110 methods plus `PATCH`; any other method is
+/// named `HTTP`, so extension methods can't grow the span-name set. Unlike `method_label`,
+/// `HEAD` and `OPTIONS` (e.g. CORS preflights) keep their own names.
+fn span_name_method(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::CONNECT => "CONNECT",
+        Method::OPTIONS => "OPTIONS",
+        Method::TRACE => "TRACE",
+        Method::PATCH => "PATCH",
+        _ => "HTTP",
+    }
+}
+
+/// Bounded metric label for a request
+///
+/// The known endpoints are the registered route patterns: a normalized path is kept only when
+/// it equals the pattern actix matched (e.g. `/api/users/{id}`). Unmatched routes and
+/// non-ID-like parameters collapse to `other`, so random client paths can't grow the label set.
+fn endpoint_label(req: &ServiceRequest) -> String {
+    let endpoint = normalize_endpoint(req.path());
+
+    match req.match_pattern() {
+        Some(pattern) if pattern == endpoint => endpoint,
+        _ => OTHER_ENDPOINT.to_string(),
+    }
+}
+
+/// Length of a MongoDB-style ObjectId in hex characters
+const OBJECT_ID_LEN: usize = 24;
+
+/// Whether a path segment looks like an identifier rather than a route word
+///
+/// Matches integers of any width, UUIDs, and 24-char hex ObjectIds.
+fn is_id_segment(segment: &str) -> bool {
+    segment.bytes().all(|b| b.is_ascii_digit())
+        || Uuid::parse_str(segment).is_ok()
+        || (segment.len() == OBJECT_ID_LEN && segment.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
 impl<S, B> Transform<S, ServiceRequest> for RequestIdMiddleware
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
-    B: 'static,
+    B: MessageBody + 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<BoxBody>;
     type Error = Error;
     type InitError = ();
     type Transform = RequestIdMiddlewareService<S>;
@@ -56,93 +578,304 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         std::future::ready(Ok(RequestIdMiddlewareService {
             service: Rc::new(service),
+            header: self.header.clone(),
+            slow_request_threshold: self.slow_request_threshold,
+            span_attributes: self.span_attributes.clone(),
+            capture_error_bodies: self.capture_error_bodies,
         }))
     }
 }
 
 pub struct RequestIdMiddlewareService<S> {
     service: Rc<S>,
+    header: HeaderName,
+    slow_request_threshold: Duration,
+    span_attributes: Arc<[(String, String)]>,
+    capture_error_bodies: bool,
 }
 
 impl<S, B> Service<ServiceRequest> for RequestIdMiddlewareService<S>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
-    B: 'static,
+    B: MessageBody + 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<BoxBody>;
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     forward_ready!(service);
 
-    fn call(&self, req: ServiceRequest) -> Self::Future {
-        let request_id = Uuid::new_v4().to_string();
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let entered_at = Instant::now();
+        let header = self.header.clone();
+        let request_id = resolve_request_id(&req, &header);
         let method = req.method().to_string();
         let path = req.path().to_string();
-        let endpoint = normalize_endpoint(&path);
+        let endpoint = endpoint_label(&req);
+        let route = req.match_pattern();
+        let request_size = request_content_length(&req);
+        let client_address = client_address(&req);
+        let has_origin = req.headers().contains_key(ORIGIN);
 
-        // Insert request ID into request extensions
-        req.extensions_mut().insert(request_id.clone());
+        // Insert request ID into request extensions so handlers can extract it as `RequestId`
+        req.extensions_mut().insert(RequestId(request_id.clone()));
+
+        let deadline = Deadline::from_header(req.headers().get(REQUEST_DEADLINE_HEADER));
+        req.extensions_mut().insert(deadline);
+
+        // Exported name per the HTTP semantic conventions: `{method} {route}`, or just the
+        // method when no known route matched, so the name set stays bounded
+        let span_method = span_name_method(req.method());
+        let span_name = if endpoint == OTHER_ENDPOINT {
+            span_method.to_string()
+        } else {
+            format!("{span_method} {endpoint}")
+        };
 
         let span = tracing::info_span!(
             "http_request",
+            otel.name = %span_name,
             request_id = %request_id,
             method = %method,
             path = %path,
+            endpoint = %endpoint,
+            client.address = client_address.as_deref(),
+            http.user_agent = header_str(&req, USER_AGENT),
+            http.referer = header_str(&req, REFERER),
             status = tracing::field::Empty,
             duration_ms = tracing::field::Empty,
+            ttfb_ms = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+            otel.status_message = tracing::field::Empty,
+            timed_out = tracing::field::Empty,
+            deadline.remaining_ms = deadline.remaining().map(|left| left.as_millis() as u64),
+            deadline.exceeded = tracing::field::Empty,
+            http.response.content_type = tracing::field::Empty,
+            http.response.encoding = tracing::field::Empty,
+            http.response.compressed_size = tracing::field::Empty,
+            http.response.uncompressed_size = tracing::field::Empty,
+            cors.origin_allowed = tracing::field::Empty,
+            http.request.body = tracing::field::Empty,
+            http.request.body.truncated = tracing::field::Empty,
+            // OpenTelemetry HTTP semantic-convention keys, duplicating the fields above for
+            // backends whose dashboards expect them; `http.route` is only set for known routes
+            http.request.method = %method,
+            http.route = route.as_deref(),
+            url.path = %path,
+            http.response.status_code = tracing::field::Empty,
+        );
+
+        // Continue the caller's trace when a valid `traceparent` header is present
+        if let Some(parent_context) = propagation::extract_context(req.headers()) {
+            span.set_parent(parent_context);
+        }
+
+        for (key, value) in self.span_attributes.iter() {
+            span.set_attribute(key.clone(), value.clone());
+        }
+
+        // Covers this middleware's own work up to calling the inner service; created only once
+        // the request span's parent is final, so it lands in the same trace
+        let overhead_span = tracing::info_span!(
+            parent: &span,
+            "middleware.overhead",
+            overhead_us = tracing::field::Empty
         );
 
-        info!("Request started");
+        span.in_scope(|| {
+            info!("Request started");
+            debug!(headers = ?redacted_headers(req.headers()), "Request headers");
+        });
+
+        // Only copied when enabled; recorded on the span only if the request fails
+        let captured_body = self
+            .capture_error_bodies
+            .then(|| capture_request_body(&mut req));
 
         let service = self.service.clone();
-        let start_time = std::time::Instant::now();
+        let start_time = Instant::now();
+        let slow_request_threshold = self.slow_request_threshold;
 
         let span_clone = span.clone();
-        let method_label = method.clone();
+        let method_label = method_label(req.method()).to_string();
         let endpoint_label = endpoint.clone();
 
-        metrics::track_request_start(&method_label, &endpoint_label);
+        let in_flight = metrics::track_request_start(&method_label, &endpoint_label);
 
         Box::pin(
             async move {
-                let result = service.call(req).await;
+                // Held across the call so the gauge is decremented however the future ends;
+                // successful responses hand it to their body so streaming still counts
+                let in_flight = in_flight;
+                let mut cancellation = CancellationGuard {
+                    method: method_label.clone(),
+                    endpoint: endpoint_label.clone(),
+                    start_time,
+                    span: span_clone.clone(),
+                    armed: true,
+                };
+
+                overhead_span.record("overhead_us", entered_at.elapsed().as_micros() as u64);
+                drop(overhead_span);
+
+                // A panicking handler would otherwise drop the connection and skip the status
+                // and metrics below; turn it into a 500 that flows through the error path
+                let result = match AssertUnwindSafe(async move { service.call(req).await })
+                    .catch_unwind()
+                    .await
+                {
+                    Ok(result) => result,
+                    Err(payload) => {
+                        let message = panic_message(payload.as_ref());
+                        error!(panic.message = %message, "Handler panicked");
+                        // Counted as internal so alerts on internal errors cover panics too
+                        metrics::track_app_error(ErrorKind::Internal.metric_label());
+
+                        Err(ApiError::internal(
+                            "Internal server error",
+                            &RequestId(request_id.clone()),
+                        )
+                        .into())
+                    }
+                };
                 let elapsed = start_time.elapsed();
                 let duration_ms = elapsed.as_millis() as u64;
 
+                // Both branches below record the result exactly once, panics and timeouts
+                // included (as 500 and 503 errors)
+                cancellation.disarm();
+
                 match result {
-                    Ok(res) => {
+                    Ok(mut res) => {
                         let status = res.status();
 
+                        insert_request_id_header(res.headers_mut(), &header, &request_id);
+                        propagation::inject_context(res.headers_mut());
+
+                        // The head is ready now; `ResponseBody` records `duration_ms` once the
+                        // body has been sent, so the gap between the two is streaming time
                         span_clone.record("status", status.as_u16());
-                        span_clone.record("duration_ms", duration_ms);
+                        span_clone.record("http.response.status_code", status.as_u16());
+                        span_clone.record("ttfb_ms", duration_ms);
+
+                        // Bodyless responses such as 204 carry no content type; leave the field unset
+                        if let Some(content_type) = res
+                            .headers()
+                            .get(CONTENT_TYPE)
+                            .and_then(|value| value.to_str().ok())
+                        {
+                            span_clone.record("http.response.content_type", content_type);
+                        }
+
+                        // The CORS layer echoes allowed origins and omits the header otherwise
+                        if has_origin {
+                            span_clone.record(
+                                "cors.origin_allowed",
+                                res.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN),
+                            );
+                        }
+
+                        // Handler errors arrive here already rendered; count the ones we classify
+                        if let Some(api_error) = res
+                            .response()
+                            .error()
+                            .and_then(|err| err.as_error::<ApiError>())
+                        {
+                            metrics::track_app_error(api_error.kind.metric_label());
+                            if api_error.kind == ErrorKind::DeadlineExceeded {
+                                span_clone.record("deadline.exceeded", true);
+                            }
+                        }
+
+                        record_response_status(&span_clone, status, || {
+                            res.response()
+                                .error()
+                                .map(ToString::to_string)
+                                .unwrap_or_else(|| status.to_string())
+                        });
 
                         metrics::track_request_result(
                             &method_label,
                             &endpoint_label,
                             status.as_u16(),
                             elapsed,
+                            propagation::trace_id(&span_clone).as_deref(),
+                        );
+                        // Compressed bodies are streamed, so fall back to the pre-compression size
+                        let response_size = response_body_size(&res).or_else(|| {
+                            res.request()
+                                .extensions()
+                                .get::<UncompressedSize>()
+                                .map(|size| size.0)
+                        });
+                        metrics::track_payload_sizes(
+                            &method_label,
+                            &endpoint_label,
+                            request_size,
+                            response_size,
                         );
 
-                        info!("Request completed with status {}", status);
+                        log_access(&method, &path, status.as_u16(), duration_ms, &request_id);
+                        log_if_slow(slow_request_threshold, elapsed, &method, &endpoint, &path);
+                        record_captured_body(&span_clone, captured_body.as_ref(), status);
 
-                        Ok(res)
+                        let encoding = res
+                            .headers()
+                            .get(CONTENT_ENCODING)
+                            .and_then(|value| value.to_str().ok())
+                            .map(str::to_string);
+
+                        if let Some(encoding) = &encoding {
+                            span_clone.record("http.response.encoding", encoding.as_str());
+                        }
+
+                        let span = span_clone.clone();
+                        Ok(res.map_body(|_, body| {
+                            ResponseBody {
+                                body: body.boxed(),
+                                bytes: 0,
+                                compressed: encoding.is_some(),
+                                start_time,
+                                span,
+                                _in_flight: in_flight,
+                            }
+                            .boxed()
+                        }))
                     }
                     Err(err) => {
-                        span_clone.record("status", StatusCode::INTERNAL_SERVER_ERROR.as_u16());
+                        let status = err.as_response_error().status_code();
+
+                        // Error responses are rendered in one go, so both times coincide
+                        span_clone.record("status", status.as_u16());
+                        span_clone.record("http.response.status_code", status.as_u16());
+                        span_clone.record("ttfb_ms", duration_ms);
                         span_clone.record("duration_ms", duration_ms);
+                        record_response_status(
+                            &span_clone,
+                            err.as_response_error().status_code(),
+                            || err.to_string(),
+                        );
 
                         metrics::track_request_result(
                             &method_label,
                             &endpoint_label,
-                            StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                            status.as_u16(),
                             elapsed,
+                            propagation::trace_id(&span_clone).as_deref(),
                         );
 
                         warn!("Request failed: {}", err);
+                        log_access(&method, &path, status.as_u16(), duration_ms, &request_id);
+                        log_if_slow(slow_request_threshold, elapsed, &method, &endpoint, &path);
+                        record_captured_body(&span_clone, captured_body.as_ref(), status);
 
-                        Err(err)
+                        // Attach the header to the response actix will render for this error
+                        let mut response = err.error_response();
+                        insert_request_id_header(response.headers_mut(), &header, &request_id);
+                        propagation::inject_context(response.headers_mut());
+
+                        Err(InternalError::from_response(err, response).into())
                     }
                 }
             }
@@ -151,5 +884,1668 @@ where
     }
 }
 
-// Helper to instrument futures
-use tracing::Instrument;
+/// Error for a request turned away by middleware, rendered with the shared `ApiError` body
+///
+/// Counts the kind in `app_errors_total` and returns the rendered response as the error, after
+/// `customize` has adjusted it (e.g. with a `Retry-After` header).
+fn reject(
+    kind: ErrorKind,
+    message: impl Into<String>,
+    request_id: &RequestId,
+    customize: impl FnOnce(&mut HttpResponse),
+) -> Error {
+    metrics::track_app_error(kind.metric_label());
+
+    let error = ApiError::new(kind, message, request_id);
+    let mut response = error.error_response();
+    customize(&mut response);
+
+    InternalError::from_response(error, response).into()
+}
+
+/// Request ID set by `RequestIdMiddleware`, empty when it isn't mounted
+fn request_id_of(req: &ServiceRequest) -> RequestId {
+    req.extensions()
+        .get::<RequestId>()
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Middleware that fails requests running longer than a configured timeout with a 503
+///
+/// Wrap it inside `RequestIdMiddleware` so the timeout is recorded on the request span. The
+/// timeout is surfaced as an error carrying the 503 response, since the `ServiceRequest` (and
+/// its `HttpRequest`) is owned by the inner future and can't be cloned before routing.
+pub struct TimeoutMiddleware {
+    timeout: Duration,
+}
+
+impl TimeoutMiddleware {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for TimeoutMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = TimeoutMiddlewareService<S>;
+    type Future = std::future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        std::future::ready(Ok(TimeoutMiddlewareService {
+            service: Rc::new(service),
+            timeout: self.timeout,
+        }))
+    }
+}
+
+pub struct TimeoutMiddlewareService<S> {
+    service: Rc<S>,
+    timeout: Duration,
+}
+
+impl<S, B> Service<ServiceRequest> for TimeoutMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let method = method_label(req.method());
+        let endpoint = endpoint_label(&req);
+        let request_id = request_id_of(&req);
+        let timeout = self.timeout;
+        let span = tracing::Span::current();
+
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            match tokio::time::timeout(timeout, fut).await {
+                Ok(result) => result,
+                Err(_) => {
+                    span.record("timed_out", true);
+                    metrics::track_request_timeout(method, &endpoint);
+
+                    let timeout_ms = timeout.as_millis() as u64;
+                    warn!(timeout_ms, "Request timed out");
+
+                    Err(reject(
+                        ErrorKind::Timeout,
+                        format!("Request timed out after {timeout_ms}ms"),
+                        &request_id,
+                        |_| {},
+                    ))
+                }
+            }
+        })
+    }
+}
+
+/// Client buckets kept at most; a new client beyond this first makes room
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Share of the cap kept when making room, so the O(n) pass runs once per batch of new
+/// clients rather than on every request at the cap
+const EVICTION_TARGET_PERCENT: usize = 90;
+
+/// Longest wait reported to a limited client; tiny rates would otherwise overflow `Duration`
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(3600);
+
+/// Token bucket state for a single client
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Per-client token buckets shared by every worker
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    max_clients: usize,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        Self {
+            requests_per_second,
+            burst: f64::from(burst.max(1)),
+            max_clients: MAX_TRACKED_CLIENTS,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for `client`, or return how long until one is available
+    fn try_acquire(&self, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // Client keys come from headers such as `X-Forwarded-For`, so bound the map
+        if buckets.len() >= self.max_clients && !buckets.contains_key(client) {
+            self.make_room(&mut buckets, now);
+        }
+
+        let bucket = buckets
+            .entry(client.to_string())
+            .or_insert_with(|| TokenBucket {
+                tokens: self.burst,
+                last_refill: now,
+            });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - bucket.tokens) / self.requests_per_second;
+            Err(Duration::try_from_secs_f64(wait)
+                .map_or(MAX_RETRY_AFTER, |wait| wait.min(MAX_RETRY_AFTER)))
+        }
+    }
+
+    /// Shrink `buckets` below the cap: refilled buckets go first (a fresh bucket behaves the
+    /// same), then the least recently seen clients, which restart with a full burst
+    fn make_room(&self, buckets: &mut HashMap<String, TokenBucket>, now: Instant) {
+        let (rate, burst) = (self.requests_per_second, self.burst);
+        buckets.retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.tokens + elapsed * rate < burst
+        });
+
+        let target = self.max_clients * EVICTION_TARGET_PERCENT / 100;
+        if buckets.len() > target {
+            let excess = buckets.len() - target;
+            let mut last_seen: Vec<Instant> =
+                buckets.values().map(|bucket| bucket.last_refill).collect();
+            let cutoff = *last_seen.select_nth_unstable(excess - 1).1;
+            buckets.retain(|_, bucket| bucket.last_refill > cutoff);
+        }
+    }
+}
+
+/// Middleware that rejects clients exceeding their token bucket with a 429
+///
+/// Clients are keyed by IP, using `X-Forwarded-For`/`Forwarded` when behind a proxy.
+/// Without a limiter every request passes straight through.
+pub struct RateLimitMiddleware {
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limiter: Option<Arc<RateLimiter>>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimitMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddlewareService<S>;
+    type Future = std::future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        std::future::ready(Ok(RateLimitMiddlewareService {
+            service: Rc::new(service),
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddlewareService<S> {
+    service: Rc<S>,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let Some(limiter) = &self.limiter else {
+            return Box::pin(self.service.call(req));
+        };
+
+        let client_ip = req
+            .connection_info()
+            .realip_remote_addr()
+            .unwrap_or("unknown")
+            .to_string();
+
+        match limiter.try_acquire(&client_ip) {
+            Ok(()) => Box::pin(self.service.call(req)),
+            Err(retry_after) => {
+                let retry_after_secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+
+                metrics::track_rate_limited(method_label(req.method()), &endpoint_label(&req));
+                warn!(
+                    rate_limited = true,
+                    client_ip = %client_ip,
+                    retry_after_secs,
+                    "Request rate limited"
+                );
+
+                let error = reject(
+                    ErrorKind::RateLimited,
+                    format!("Too many requests, retry after {retry_after_secs}s"),
+                    &request_id_of(&req),
+                    |response| {
+                        response
+                            .headers_mut()
+                            .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+                    },
+                );
+
+                Box::pin(std::future::ready(Err(error)))
+            }
+        }
+    }
+}
+
+/// Set once shutdown begins; from then on new requests are turned away with a 503
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+/// Seconds clients are told to wait before retrying against another instance
+const DRAIN_RETRY_AFTER_SECS: u64 = 5;
+
+/// Start rejecting new requests; called by the shutdown handler before draining
+pub fn start_draining() {
+    DRAINING.store(true, Ordering::Release);
+}
+
+/// Whether the server is shutting down and rejecting new requests
+pub fn is_draining() -> bool {
+    DRAINING.load(Ordering::Acquire)
+}
+
+/// Middleware answering 503 with `Retry-After` to requests that arrive while draining
+///
+/// Draining starts `APP_DRAIN_DELAY_SECS` before the listener closes. Requests arriving in
+/// that window get a clear 503 and `Connection: close` so clients reconnect elsewhere, while
+/// requests already past this middleware run to completion.
+pub struct DrainMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for DrainMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = DrainMiddlewareService<S>;
+    type Future = std::future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        std::future::ready(Ok(DrainMiddlewareService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct DrainMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for DrainMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if !is_draining() {
+            return Box::pin(self.service.call(req));
+        }
+
+        warn!(
+            draining = true,
+            retry_after_secs = DRAIN_RETRY_AFTER_SECS,
+            "Rejecting request, server is shutting down"
+        );
+
+        let error = reject(
+            ErrorKind::ShuttingDown,
+            format!("Server is shutting down, retry after {DRAIN_RETRY_AFTER_SECS}s"),
+            &request_id_of(&req),
+            |response| {
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(DRAIN_RETRY_AFTER_SECS));
+                response
+                    .head_mut()
+                    .set_connection_type(ConnectionType::Close);
+            },
+        );
+
+        Box::pin(std::future::ready(Err(error)))
+    }
+}
+
+// Helper to instrument futures
+use tracing::Instrument;
+
+/// Response body size before compression, left in request extensions for `RequestIdMiddleware`
+struct UncompressedSize(u64);
+
+/// Middleware recording the response size before `Compress` encodes it
+///
+/// Wrap it inside `middleware::Compress`, which itself sits inside `RequestIdMiddleware`; the
+/// size is recorded on the request span as `http.response.uncompressed_size`.
+pub struct UncompressedSizeMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for UncompressedSizeMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = UncompressedSizeMiddlewareService<S>;
+    type Future = std::future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        std::future::ready(Ok(UncompressedSizeMiddlewareService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct UncompressedSizeMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for UncompressedSizeMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let span = tracing::Span::current();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+
+            if let Some(size) = response_body_size(&res) {
+                span.record("http.response.uncompressed_size", size);
+                res.request()
+                    .extensions_mut()
+                    .insert(UncompressedSize(size));
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+/// Body wrapper timing the full response and, for compressed bodies, counting bytes sent
+///
+/// Holding the span keeps the request span open until the body is done, and holding the
+/// in-flight guard keeps streaming responses counted in `http_requests_in_flight`.
+/// `duration_ms` is recorded on drop rather than at end of stream because actix never polls
+/// empty bodies.
+struct ResponseBody {
+    body: BoxBody,
+    bytes: u64,
+    compressed: bool,
+    start_time: Instant,
+    span: tracing::Span,
+    _in_flight: metrics::InFlightGuard,
+}
+
+impl Drop for ResponseBody {
+    fn drop(&mut self) {
+        self.span
+            .record("duration_ms", self.start_time.elapsed().as_millis() as u64);
+    }
+}
+
+impl MessageBody for ResponseBody {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let poll = Pin::new(&mut self.body).poll_next(cx);
+
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => self.bytes += chunk.len() as u64,
+            Poll::Ready(None) if self.compressed => {
+                self.span
+                    .record("http.response.compressed_size", self.bytes);
+            }
+            _ => {}
+        }
+
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        TestTracing, attribute, event_attribute, lock_metrics, metric_value,
+    };
+    use actix_web::{App, test as actix_test, web};
+
+    const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+    async fn echo_request_id(request_id: RequestId) -> HttpResponse {
+        HttpResponse::Ok().body(request_id.0)
+    }
+
+    fn request_id_middleware() -> RequestIdMiddleware {
+        RequestIdMiddleware::new(REQUEST_ID_HEADER, Duration::from_secs(60))
+    }
+
+    async fn not_found() -> actix_web::Result<HttpResponse> {
+        Err(actix_web::error::ErrorNotFound("missing"))
+    }
+
+    async fn sets_own_request_id() -> HttpResponse {
+        HttpResponse::Ok()
+            .insert_header((REQUEST_ID_HEADER, "from-handler"))
+            .finish()
+    }
+
+    #[test]
+    fn reuses_inbound_request_id() {
+        let req = actix_test::TestRequest::default()
+            .insert_header((REQUEST_ID_HEADER, "gateway-42"))
+            .to_srv_request();
+
+        assert_eq!(resolve_request_id(&req, &REQUEST_ID_HEADER), "gateway-42");
+    }
+
+    #[test]
+    fn generates_request_id_when_absent_or_invalid() {
+        let too_long = "x".repeat(MAX_REQUEST_ID_LEN + 1);
+        for header in [None, Some(""), Some("has space"), Some(too_long.as_str())] {
+            let mut req = actix_test::TestRequest::default();
+            if let Some(value) = header {
+                req = req.insert_header((REQUEST_ID_HEADER, value));
+            }
+
+            let request_id = resolve_request_id(&req.to_srv_request(), &REQUEST_ID_HEADER);
+            assert!(
+                Uuid::parse_str(&request_id).is_ok(),
+                "{header:?} -> {request_id}"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn echoes_request_id_recorded_on_span() {
+        // Error responses bump the global `app_errors_total`
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/echo", web::get().to(echo_request_id))
+                .route("/missing", web::get().to(not_found)),
+        )
+        .await;
+
+        for uri in ["/echo", "/missing"] {
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            let res = actix_test::call_service(&app, req).await;
+            let header = res.headers().get(REQUEST_ID_HEADER).cloned();
+            drop(res);
+
+            let span = tracing.request_span(uri).unwrap();
+            let recorded = attribute(&span, "request_id").unwrap();
+            assert_eq!(header.unwrap().to_str().unwrap(), recorded, "{uri}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn keeps_request_id_header_set_by_handler() {
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/own", web::get().to(sets_own_request_id)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/own").to_request();
+        let res = actix_test::call_service(&app, req).await;
+
+        assert_eq!(
+            res.headers().get(REQUEST_ID_HEADER).unwrap(),
+            "from-handler"
+        );
+    }
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[actix_web::test]
+    async fn continues_inbound_trace() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/echo")
+            .insert_header(("traceparent", TRACEPARENT))
+            .to_request();
+        actix_test::call_and_read_body(&app, req).await;
+
+        let span = tracing.request_span("/echo").unwrap();
+        assert_eq!(
+            span.span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(span.parent_span_id.to_string(), "00f067aa0ba902b7");
+    }
+
+    #[actix_web::test]
+    async fn starts_new_trace_without_valid_traceparent() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/echo")
+            .insert_header(("traceparent", "00-not-a-trace-01"))
+            .to_request();
+        actix_test::call_and_read_body(&app, req).await;
+
+        let span = tracing.request_span("/echo").unwrap();
+        assert!(span.span_context.is_valid());
+        assert_eq!(span.parent_span_id, opentelemetry::trace::SpanId::INVALID);
+    }
+
+    async fn echo_baggage() -> HttpResponse {
+        HttpResponse::Ok().json(propagation::current_baggage())
+    }
+
+    #[actix_web::test]
+    async fn inbound_baggage_is_readable_in_handlers() {
+        let _tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/baggage", web::get().to(echo_baggage)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/baggage")
+            .insert_header(("traceparent", TRACEPARENT))
+            .insert_header(("baggage", "tenant.id=acme,user.tier=gold"))
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            body,
+            serde_json::json!({ "tenant.id": "acme", "user.tier": "gold" })
+        );
+
+        // Baggage alone still reaches the handler, under a new trace
+        let req = actix_test::TestRequest::get()
+            .uri("/baggage")
+            .insert_header(("baggage", "tenant.id=acme"))
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["tenant.id"], "acme");
+    }
+
+    async fn fails() -> actix_web::Result<HttpResponse> {
+        Err(actix_web::error::ErrorInternalServerError(
+            "database unavailable",
+        ))
+    }
+
+    #[actix_web::test]
+    async fn failing_route_exports_error_status() {
+        // Error responses bump the global `app_errors_total`
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/fails", web::get().to(fails))
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        for uri in ["/fails", "/echo"] {
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            actix_test::call_and_read_body(&app, req).await;
+        }
+
+        let failed = tracing.request_span("/fails").unwrap();
+        assert!(
+            matches!(&failed.status, opentelemetry::trace::Status::Error { description } if description.contains("database unavailable")),
+            "{:?}",
+            failed.status
+        );
+        let ok = tracing.request_span("/echo").unwrap();
+        assert_eq!(ok.status, opentelemetry::trace::Status::Ok);
+    }
+
+    #[actix_web::test]
+    async fn client_errors_leave_span_status_unset() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(RateLimitMiddleware::new(Some(Arc::new(limiter(
+                    1.0,
+                    1,
+                    MAX_TRACKED_CLIENTS,
+                )))))
+                .wrap(request_id_middleware())
+                .route("/ok", web::get().to(ok))
+                .route("/fail/{kind}", web::get().to(fails_with)),
+        )
+        .await;
+
+        // A handler's 404 is rendered into a response (and spends the only token); the rate
+        // limiter's 429 arrives as an error
+        let req = actix_test::TestRequest::get()
+            .uri("/fail/not_found")
+            .to_request();
+        actix_test::call_and_read_body(&app, req).await;
+        let req = actix_test::TestRequest::get()
+            .uri("/ok")
+            .insert_header((REQUEST_ID_HEADER, "limited"))
+            .to_request();
+        let res = actix_test::try_call_service(&app, req)
+            .await
+            .err()
+            .unwrap()
+            .error_response();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.headers().contains_key(RETRY_AFTER));
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "rate_limited");
+        assert_eq!(body["error"]["request_id"], "limited");
+
+        let limited = tracing
+            .spans()
+            .into_iter()
+            .find(|span| attribute(span, "request_id").as_deref() == Some("limited"))
+            .unwrap();
+        assert_eq!(
+            attribute(&limited, "http.response.status_code").as_deref(),
+            Some("429")
+        );
+        assert_eq!(limited.status, opentelemetry::trace::Status::Unset);
+        let not_found = tracing.request_span("/fail/not_found").unwrap();
+        assert_eq!(
+            attribute(&not_found, "http.response.status_code").as_deref(),
+            Some("404")
+        );
+        assert_eq!(not_found.status, opentelemetry::trace::Status::Unset);
+    }
+
+    #[test]
+    fn normalize_endpoint_strips_queries_and_empty_segments() {
+        for (path, expected) in [
+            ("/api/users?limit=10", "/api/users"),
+            ("/api/users?limit=10&offset=5#top", "/api/users"),
+            ("//api//users", "/api/users"),
+            ("/api/users/", "/api/users"),
+            ("/api/users//", "/api/users"),
+            ("/", "/"),
+            ("", "/"),
+            ("/?debug=1", "/"),
+        ] {
+            assert_eq!(normalize_endpoint(path), expected, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn normalize_endpoint_collapses_ids_only() {
+        for (path, expected) in [
+            ("/api/users/42", "/api/users/{id}"),
+            (
+                "/api/users/340282366920938463463374607431768211456",
+                "/api/users/{id}",
+            ),
+            ("/api/users/507f1f77bcf86cd799439011", "/api/users/{id}"),
+            (
+                "/api/users/67e55044-10b1-426f-9247-bb680e5fe0c8",
+                "/api/users/{id}",
+            ),
+            ("/api/users", "/api/users"),
+            ("/api/users/export", "/api/users/export"),
+            // Hex words and near-ObjectIds are route words, not ids
+            ("/api/cafe", "/api/cafe"),
+            (
+                "/api/users/507f1f77bcf86cd79943901",
+                "/api/users/507f1f77bcf86cd79943901",
+            ),
+            ("/api/users/user42", "/api/users/user42"),
+        ] {
+            assert_eq!(normalize_endpoint(path), expected, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn normalize_endpoint_decodes_or_rejects_escapes() {
+        for (path, expected) in [
+            ("/api/users/%34%32", "/api/users/{id}"),
+            ("/api/%75sers/42", "/api/users/{id}"),
+            ("/api/caf%C3%A9", "/api/café"),
+            // Truncated and non-hex escapes, and bytes that aren't UTF-8
+            ("/api/users/%4", "/api/users/{invalid}"),
+            ("/api/users/%zz", "/api/users/{invalid}"),
+            ("/api/users/%FF%FE", "/api/users/{invalid}"),
+        ] {
+            assert_eq!(normalize_endpoint(path), expected, "{path:?}");
+        }
+    }
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn unmatched_paths_are_labeled_other() {
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/api/widgets/{id}", web::patch().to(ok)),
+        )
+        .await;
+        let requests = |endpoint, status| {
+            metric_value(
+                "http_requests_total",
+                &[
+                    ("method", "PATCH"),
+                    ("endpoint", endpoint),
+                    ("status", status),
+                ],
+            )
+        };
+        let (known, other) = (
+            requests("/api/widgets/{id}", "200"),
+            requests("other", "404"),
+        );
+
+        for uri in ["/api/widgets/42", "/random/a1b2", "/api/widgets/42/extra"] {
+            let req = actix_test::TestRequest::patch().uri(uri).to_request();
+            actix_test::call_service(&app, req).await;
+        }
+
+        assert_eq!(requests("/api/widgets/{id}", "200"), known + 1.0);
+        assert_eq!(requests("other", "404"), other + 2.0);
+        assert_eq!(requests("/random/a1b2", "404"), 0.0);
+    }
+
+    async fn slow() -> HttpResponse {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn slow_handler_times_out() {
+        // Error responses bump the global `app_errors_total`
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(TimeoutMiddleware::new(Duration::from_millis(20)))
+                .wrap(request_id_middleware())
+                .route("/slow", web::get().to(slow))
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        // The timeout surfaces as an error carrying its response, rendered by the server
+        let req = actix_test::TestRequest::get().uri("/slow").to_request();
+        let err = actix_test::try_call_service(&app, req).await.err().unwrap();
+        let res = err.error_response();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "timeout");
+        assert_eq!(body["error"]["message"], "Request timed out after 20ms");
+        assert!(!body["error"]["request_id"].as_str().unwrap().is_empty());
+
+        let req = actix_test::TestRequest::get().uri("/echo").to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let span = tracing.request_span("/slow").unwrap();
+        assert_eq!(attribute(&span, "timed_out").as_deref(), Some("true"));
+    }
+
+    fn limiter(requests_per_second: f64, burst: u32, max_clients: usize) -> RateLimiter {
+        RateLimiter {
+            max_clients,
+            ..RateLimiter::new(requests_per_second, burst)
+        }
+    }
+
+    #[test]
+    fn rate_limiter_allows_burst_then_limits() {
+        let limiter = limiter(10.0, 3, MAX_TRACKED_CLIENTS);
+
+        for _ in 0..3 {
+            assert_eq!(limiter.try_acquire("10.0.0.1"), Ok(()));
+        }
+        let wait = limiter.try_acquire("10.0.0.1").unwrap_err();
+        assert!(
+            wait > Duration::ZERO && wait <= Duration::from_millis(100),
+            "{wait:?}"
+        );
+
+        // Buckets are per client
+        assert_eq!(limiter.try_acquire("10.0.0.2"), Ok(()));
+    }
+
+    #[test]
+    fn rate_limiter_wait_is_clamped_for_tiny_rates() {
+        let limiter = limiter(1e-20, 1, MAX_TRACKED_CLIENTS);
+
+        assert_eq!(limiter.try_acquire("10.0.0.1"), Ok(()));
+        assert_eq!(limiter.try_acquire("10.0.0.1"), Err(MAX_RETRY_AFTER));
+    }
+
+    #[test]
+    fn rate_limiter_bounds_tracked_clients() {
+        let limiter = limiter(1.0, 5, 100);
+
+        // Every client keeps a partly drained bucket, so none can simply be pruned
+        for client in 0..1_000 {
+            assert_eq!(limiter.try_acquire(&format!("10.0.{client}")), Ok(()));
+            let tracked = limiter.buckets.lock().unwrap().len();
+            assert!(tracked <= 100, "{tracked} clients tracked");
+        }
+
+        // The most recent client survived the eviction and is still being limited
+        let last = "10.0.999";
+        for _ in 0..4 {
+            assert_eq!(limiter.try_acquire(last), Ok(()));
+        }
+        assert!(limiter.try_acquire(last).is_err());
+    }
+
+    async fn panics() -> HttpResponse {
+        panic!("handler bug")
+    }
+
+    #[actix_web::test]
+    async fn in_flight_gauge_recovers_from_panics() {
+        // Error responses bump the global `app_errors_total`
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/test/panics", web::get().to(panics)),
+        )
+        .await;
+
+        for _ in 0..3 {
+            let req = actix_test::TestRequest::get()
+                .uri("/test/panics")
+                .to_request();
+            let err = actix_test::try_call_service(&app, req).await.err().unwrap();
+            assert_eq!(
+                err.error_response().status(),
+                StatusCode::INTERNAL_SERVER_ERROR
+            );
+        }
+
+        let in_flight = metric_value(
+            "http_requests_in_flight",
+            &[("method", "GET"), ("endpoint", "/test/panics")],
+        );
+        assert_eq!(in_flight, 0.0);
+    }
+
+    #[actix_web::test]
+    async fn latency_exemplar_carries_trace_id() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/echo", web::get().to(echo_request_id))
+                .route("/metrics", web::get().to(metrics::metrics_handler)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/echo").to_request();
+        actix_test::call_and_read_body(&app, req).await;
+        let trace_id = tracing
+            .request_span("/echo")
+            .unwrap()
+            .span_context
+            .trace_id()
+            .to_string();
+
+        let req = actix_test::TestRequest::get()
+            .uri("/metrics")
+            .insert_header((
+                actix_web::http::header::ACCEPT,
+                "application/openmetrics-text",
+            ))
+            .to_request();
+        let body = actix_test::call_and_read_body(&app, req).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        let exemplar = format!("# {{trace_id=\"{trace_id}\"}}");
+        assert!(
+            body.lines().any(|line| line
+                .starts_with("http_request_duration_seconds_bucket{endpoint=\"/echo\"")
+                && line.contains(&exemplar)),
+            "{body}"
+        );
+    }
+
+    async fn fails_with(
+        kind: web::Path<String>,
+        request_id: RequestId,
+    ) -> Result<HttpResponse, ApiError> {
+        let kind = match kind.as_str() {
+            "validation" => ErrorKind::Validation,
+            "not_found" => ErrorKind::NotFound,
+            _ => ErrorKind::Internal,
+        };
+        Err(ApiError::new(kind, "failed on purpose", &request_id))
+    }
+
+    #[actix_web::test]
+    async fn error_paths_bump_their_kind() {
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(TimeoutMiddleware::new(Duration::from_millis(20)))
+                .wrap(request_id_middleware())
+                .route("/fail/{kind}", web::get().to(fails_with))
+                .route("/slow", web::get().to(slow)),
+        )
+        .await;
+        let kinds = ["validation", "not_found", "internal", "timeout"];
+        let errors = |kind| metric_value("app_errors_total", &[("kind", kind)]);
+
+        for (uri, kind) in [
+            ("/fail/validation", "validation"),
+            ("/fail/not_found", "not_found"),
+            ("/fail/internal", "internal"),
+            ("/slow", "timeout"),
+        ] {
+            let before = kinds.map(errors);
+
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            let _ = actix_test::try_call_service(&app, req).await;
+
+            for (label, before) in kinds.into_iter().zip(before) {
+                let expected = if label == kind { before + 1.0 } else { before };
+                assert_eq!(errors(label), expected, "{uri} -> {label}");
+            }
+        }
+    }
+
+    #[test]
+    fn client_address_prefers_first_forwarded_hop() {
+        let peer = "10.0.0.5:41000".parse().unwrap();
+
+        let direct = actix_test::TestRequest::get()
+            .peer_addr(peer)
+            .to_srv_request();
+        assert_eq!(client_address(&direct).as_deref(), Some("10.0.0.5"));
+
+        let proxied = actix_test::TestRequest::get()
+            .peer_addr(peer)
+            .insert_header((X_FORWARDED_FOR, "203.0.113.7, 10.0.0.1"))
+            .to_srv_request();
+        assert_eq!(client_address(&proxied).as_deref(), Some("203.0.113.7"));
+    }
+
+    #[actix_web::test]
+    async fn request_span_records_user_agent_and_referer() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/echo", web::get().to(echo_request_id))
+                .route("/ok", web::get().to(ok)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/echo")
+            .insert_header((USER_AGENT, "curl/8.5.0"))
+            .insert_header((REFERER, "https://example.com/dashboard"))
+            .to_request();
+        actix_test::call_service(&app, req).await;
+        let req = actix_test::TestRequest::get()
+            .uri("/ok")
+            .insert_header((USER_AGENT, " "))
+            .to_request();
+        actix_test::call_service(&app, req).await;
+
+        let span = tracing.request_span("/echo").unwrap();
+        assert_eq!(
+            attribute(&span, "http.user_agent").as_deref(),
+            Some("curl/8.5.0")
+        );
+        assert_eq!(
+            attribute(&span, "http.referer").as_deref(),
+            Some("https://example.com/dashboard")
+        );
+        let bare = tracing.request_span("/ok").unwrap();
+        assert_eq!(attribute(&bare, "http.user_agent"), None);
+        assert_eq!(attribute(&bare, "http.referer"), None);
+    }
+
+    #[actix_web::test]
+    async fn panicking_route_is_a_recorded_500() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/test/panic-500", web::get().to(panics)),
+        )
+        .await;
+        let requests = [
+            ("method", "GET"),
+            ("endpoint", "/test/panic-500"),
+            ("status", "500"),
+        ];
+        let errors = |kind| metric_value("app_errors_total", &[("kind", kind)]);
+        let before = ["internal", "panic"].map(errors);
+
+        let req = actix_test::TestRequest::get()
+            .uri("/test/panic-500")
+            .to_request();
+        let err = actix_test::try_call_service(&app, req).await.err().unwrap();
+
+        assert_eq!(
+            err.error_response().status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(metric_value("http_requests_total", &requests), 1.0);
+        assert_eq!(errors("internal") - before[0], 1.0);
+        assert_eq!(errors("panic"), before[1], "no separate panic kind");
+        let span = tracing.request_span("/test/panic-500").unwrap();
+        let event = span
+            .events
+            .iter()
+            .find(|event| event.name == "Handler panicked")
+            .unwrap();
+        assert_eq!(
+            event_attribute(event, "panic.message").as_deref(),
+            Some("handler bug")
+        );
+    }
+
+    #[actix_web::test]
+    async fn access_event_carries_all_fields() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/echo")
+            .insert_header((REQUEST_ID_HEADER, "access-1"))
+            .to_request();
+        actix_test::call_and_read_body(&app, req).await;
+
+        let span = tracing.request_span("/echo").unwrap();
+        let access = span
+            .events
+            .iter()
+            .find(|event| event.name.starts_with("GET /echo 200"))
+            .unwrap();
+        assert_eq!(event_attribute(access, "method").as_deref(), Some("GET"));
+        assert_eq!(event_attribute(access, "path").as_deref(), Some("/echo"));
+        assert_eq!(event_attribute(access, "status").as_deref(), Some("200"));
+        assert_eq!(
+            event_attribute(access, "request_id").as_deref(),
+            Some("access-1")
+        );
+        let duration_ms: u64 = event_attribute(access, "duration_ms")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(access.name, format!("GET /echo 200 {duration_ms}ms"));
+    }
+
+    #[test]
+    fn authorization_is_redacted_in_any_case() {
+        for name in ["authorization", "Authorization", "AUTHORIZATION"] {
+            let req = actix_test::TestRequest::default()
+                .insert_header((name, "Bearer secret"))
+                .insert_header(("Accept", "application/json"))
+                .to_http_request();
+
+            let headers = redacted_headers(req.headers());
+
+            assert_eq!(headers["authorization"], REDACTED, "{name}");
+            assert_eq!(headers["accept"], "application/json", "{name}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn global_attributes_land_on_request_span() {
+        let tracing = TestTracing::install();
+        let attributes = vec![
+            ("region".to_string(), "eu-west-1".to_string()),
+            ("cluster".to_string(), "prod".to_string()),
+        ];
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware().with_span_attributes(attributes.into()))
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/echo").to_request();
+        actix_test::call_and_read_body(&app, req).await;
+
+        let span = tracing.request_span("/echo").unwrap();
+        assert_eq!(attribute(&span, "region").as_deref(), Some("eu-west-1"));
+        assert_eq!(attribute(&span, "cluster").as_deref(), Some("prod"));
+    }
+
+    #[actix_web::test]
+    async fn custom_request_id_header_is_read_and_echoed() {
+        let header = HeaderName::from_static("x-correlation-id");
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(RequestIdMiddleware::new(
+                    header.clone(),
+                    Duration::from_secs(60),
+                ))
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/echo")
+            .insert_header((header.clone(), "corr-7"))
+            .insert_header((REQUEST_ID_HEADER, "ignored"))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+
+        assert_eq!(res.headers().get(&header).unwrap(), "corr-7");
+        assert!(!res.headers().contains_key(REQUEST_ID_HEADER));
+        assert_eq!(actix_test::read_body(res).await, "corr-7");
+    }
+
+    #[actix_web::test]
+    async fn compressed_response_records_encoding() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(UncompressedSizeMiddleware)
+                .wrap(actix_web::middleware::Compress::default())
+                .wrap(request_id_middleware())
+                .route(
+                    "/text",
+                    web::get().to(|| async { HttpResponse::Ok().body("otel ".repeat(1000)) }),
+                ),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/text")
+            .insert_header(("accept-encoding", "gzip"))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+        let body = actix_test::read_body(res).await;
+
+        let span = tracing.request_span("/text").unwrap();
+        assert_eq!(
+            attribute(&span, "http.response.encoding").as_deref(),
+            Some("gzip")
+        );
+        assert_eq!(
+            attribute(&span, "http.response.uncompressed_size").as_deref(),
+            Some("5000")
+        );
+        assert_eq!(
+            attribute(&span, "http.response.compressed_size"),
+            Some(body.len().to_string())
+        );
+        assert!(body.len() < 5000);
+    }
+
+    #[actix_web::test]
+    async fn cors_admits_only_configured_origins() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(cors(
+                    &["https://app.example.com".to_string()],
+                    &REQUEST_ID_HEADER,
+                ))
+                .wrap(request_id_middleware())
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        for (origin, allowed, request_id) in [
+            ("https://app.example.com", true, "cors-allowed"),
+            ("https://evil.example.com", false, "cors-denied"),
+        ] {
+            let req = actix_test::TestRequest::get()
+                .uri("/echo")
+                .insert_header((ORIGIN, origin))
+                .insert_header((REQUEST_ID_HEADER, request_id))
+                .to_request();
+            let res = actix_test::call_service(&app, req).await;
+
+            assert_eq!(
+                res.headers()
+                    .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+                    .and_then(|value| value.to_str().ok()),
+                allowed.then_some(origin),
+                "{origin}"
+            );
+            // The request span ends once the body has been sent
+            actix_test::read_body(res).await;
+            let span = tracing
+                .spans()
+                .into_iter()
+                .find(|span| attribute(span, "request_id").as_deref() == Some(request_id))
+                .unwrap();
+            assert_eq!(
+                attribute(&span, "cors.origin_allowed"),
+                Some(allowed.to_string()),
+                "{origin}"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn json_response_records_content_type() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(App::new().wrap(request_id_middleware()).route(
+            "/json",
+            web::get().to(|| async { HttpResponse::Ok().json(serde_json::json!({ "ok": true })) }),
+        ))
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/json").to_request();
+        actix_test::call_and_read_body(&app, req).await;
+
+        let span = tracing.request_span("/json").unwrap();
+        assert_eq!(
+            attribute(&span, "http.response.content_type").as_deref(),
+            Some("application/json")
+        );
+    }
+
+    #[actix_web::test]
+    async fn ttfb_and_duration_are_both_recorded() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(App::new().wrap(request_id_middleware()).route(
+            "/stream",
+            web::get().to(|| async {
+                // The head goes out at once; the last chunk only after a pause
+                let chunks = futures::stream::iter([Bytes::from_static(b"first")]).chain(
+                    futures::stream::once(async {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Bytes::from_static(b"last")
+                    }),
+                );
+                HttpResponse::Ok().streaming(chunks.map(Ok::<_, Error>))
+            }),
+        ))
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/stream").to_request();
+        actix_test::call_and_read_body(&app, req).await;
+
+        let span = tracing.request_span("/stream").unwrap();
+        let field = |key| -> u64 { attribute(&span, key).unwrap().parse().unwrap() };
+        let (ttfb_ms, duration_ms) = (field("ttfb_ms"), field("duration_ms"));
+        assert!(ttfb_ms < 50, "ttfb_ms={ttfb_ms}");
+        assert!(duration_ms >= 50, "duration_ms={duration_ms}");
+    }
+
+    #[actix_web::test]
+    async fn draining_turns_new_requests_away() {
+        // Draining bumps the global `app_errors_total`; no other test mounts `DrainMiddleware`
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(DrainMiddleware)
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+        let get = || actix_test::TestRequest::get().uri("/echo").to_request();
+
+        let res = actix_test::call_service(&app, get()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        start_draining();
+        let res = actix_test::try_call_service(&app, get()).await;
+        DRAINING.store(false, Ordering::Release);
+
+        let res = res.map_or_else(|err| err.error_response(), |res| res.into_parts().1);
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            res.headers().get(RETRY_AFTER).unwrap(),
+            &DRAIN_RETRY_AFTER_SECS.to_string()
+        );
+        assert_eq!(res.head().connection_type(), ConnectionType::Close);
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "shutting_down");
+
+        let res = actix_test::call_service(&app, get()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn unusual_methods_are_labeled_other() {
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/any-method", web::route().to(echo_request_id)),
+        )
+        .await;
+        let requests = |method| {
+            metric_value(
+                "http_requests_total",
+                &[("method", method), ("endpoint", "/any-method")],
+            )
+        };
+        let before = requests("OTHER");
+
+        let req = actix_test::TestRequest::default()
+            .method(Method::from_bytes(b"PROPFIND").unwrap())
+            .uri("/any-method")
+            .to_request();
+        actix_test::call_and_read_body(&app, req).await;
+
+        assert_eq!(requests("OTHER"), before + 1.0);
+        assert_eq!(requests("PROPFIND"), 0.0);
+    }
+
+    #[actix_web::test]
+    async fn middleware_overhead_is_recorded() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/echo", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/echo").to_request();
+        let started = Instant::now();
+        actix_test::call_and_read_body(&app, req).await;
+        let elapsed = started.elapsed();
+
+        let request = tracing.request_span("/echo").unwrap();
+        let overhead = &tracing.spans_named("middleware.overhead")[0];
+        assert_eq!(overhead.parent_span_id, request.span_context.span_id());
+        let overhead_us: u64 = attribute(overhead, "overhead_us")
+            .expect("overhead_us recorded")
+            .parse()
+            .expect("a non-negative integer");
+        // The overhead starts before `duration_ms` does, so bound it by the whole call instead
+        assert!(u128::from(overhead_us) <= elapsed.as_micros());
+    }
+
+    #[actix_web::test]
+    async fn request_span_records_path_and_endpoint() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/users/{id}", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/users/42").to_request();
+        actix_test::call_and_read_body(&app, req).await;
+
+        let span = tracing.request_span("/users/42").unwrap();
+        assert_eq!(attribute(&span, "path").as_deref(), Some("/users/42"));
+        assert_eq!(attribute(&span, "endpoint").as_deref(), Some("/users/{id}"));
+    }
+
+    #[actix_web::test]
+    async fn request_span_is_named_method_and_route() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/users/{id}", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        for uri in ["/users/42", "/nowhere"] {
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            actix_test::call_and_read_body(&app, req).await;
+        }
+
+        let matched = tracing.request_span("/users/42").unwrap();
+        assert_eq!(matched.name, "GET /users/{id}");
+        // Unknown routes keep the name set bounded: the method alone
+        let unmatched = tracing.request_span("/nowhere").unwrap();
+        assert_eq!(unmatched.name, "GET");
+    }
+
+    #[actix_web::test]
+    async fn only_unknown_methods_span_as_http() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/users/{id}", web::route().to(echo_request_id)),
+        )
+        .await;
+
+        for (method, uri) in [
+            (Method::from_bytes(b"FOO").unwrap(), "/users/42"),
+            (Method::HEAD, "/users/7"),
+        ] {
+            let req = actix_test::TestRequest::default()
+                .method(method)
+                .uri(uri)
+                .to_request();
+            actix_test::call_and_read_body(&app, req).await;
+        }
+
+        let custom = tracing.request_span("/users/42").unwrap();
+        assert_eq!(custom.name, "HTTP /users/{id}");
+        let head = tracing.request_span("/users/7").unwrap();
+        assert_eq!(head.name, "HEAD /users/{id}");
+        assert_eq!(
+            attribute(&head, "http.request.method").as_deref(),
+            Some("HEAD")
+        );
+    }
+
+    #[actix_web::test]
+    async fn request_span_carries_semantic_convention_keys() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(request_id_middleware())
+                .route("/users/{id}", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get().uri("/users/42").to_request();
+        actix_test::call_and_read_body(&app, req).await;
+
+        let span = tracing.request_span("/users/42").unwrap();
+        for (key, value) in [
+            ("http.request.method", "GET"),
+            ("http.route", "/users/{id}"),
+            ("url.path", "/users/42"),
+            ("http.response.status_code", "200"),
+        ] {
+            assert_eq!(attribute(&span, key).as_deref(), Some(value), "{key}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn requests_over_the_threshold_warn() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(RequestIdMiddleware::new(
+                    REQUEST_ID_HEADER,
+                    Duration::from_millis(20),
+                ))
+                .route("/echo", web::get().to(echo_request_id))
+                .route(
+                    "/sluggish",
+                    web::get().to(|| async {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        HttpResponse::Ok().finish()
+                    }),
+                ),
+        )
+        .await;
+
+        for uri in ["/echo", "/sluggish"] {
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            actix_test::call_and_read_body(&app, req).await;
+        }
+
+        let slow_warning = |path| {
+            tracing
+                .request_span(path)
+                .unwrap()
+                .events
+                .iter()
+                .find(|event| event.name.starts_with("Slow request"))
+                .cloned()
+        };
+        assert!(slow_warning("/echo").is_none());
+        let warning = slow_warning("/sluggish").expect("slow_request warning");
+        assert_eq!(
+            event_attribute(&warning, "threshold_ms").as_deref(),
+            Some("20")
+        );
+        let duration_ms: u64 = event_attribute(&warning, "duration_ms")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(duration_ms >= 50);
+    }
+
+    #[actix_web::test]
+    async fn each_outcome_is_observed_once() {
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(TimeoutMiddleware::new(Duration::from_millis(20)))
+                .wrap(request_id_middleware())
+                .route("/ok", web::get().to(ok))
+                .route(
+                    "/invalid",
+                    web::get().to(|request_id: RequestId| async move {
+                        Err::<HttpResponse, _>(ApiError::new(
+                            ErrorKind::Validation,
+                            "failed on purpose",
+                            &request_id,
+                        ))
+                    }),
+                )
+                .route("/slow", web::get().to(slow))
+                .route("/panic", web::get().to(panics)),
+        )
+        .await;
+
+        for (uri, endpoint, status) in [
+            ("/ok", "/ok", "200"),
+            ("/invalid", "/invalid", "400"),
+            ("/slow", "/slow", "503"),
+            ("/panic", "/panic", "500"),
+        ] {
+            let observations = |status| {
+                metric_value(
+                    "http_request_duration_seconds",
+                    &[
+                        ("method", "GET"),
+                        ("endpoint", endpoint),
+                        ("status", status),
+                    ],
+                )
+            };
+            let before = [status, "499"].map(observations);
+
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            match actix_test::try_call_service(&app, req).await {
+                Ok(res) => drop(actix_test::read_body(res).await),
+                Err(err) => assert_eq!(err.error_response().status().as_str(), status),
+            }
+
+            assert_eq!(observations(status), before[0] + 1.0, "{uri}");
+            assert_eq!(observations("499"), before[1], "{uri} cancelled");
+        }
+    }
+}
@@ -0,0 +1,76 @@
+/// Per-request deadlines for cooperative cancellation
+///
+/// Callers send `X-Request-Deadline` as unix milliseconds. `RequestIdMiddleware` parses it into
+/// request extensions and records the remaining budget on the request span; handlers take a
+/// `Deadline` argument and call `check` before expensive work, answering 504 once the caller
+/// has stopped waiting instead of computing a result nobody will read.
+use crate::error::{ApiError, ErrorKind, RequestId};
+use actix_web::{FromRequest, HttpMessage, HttpRequest, dev::Payload, http::header::HeaderValue};
+use std::{
+    convert::Infallible,
+    future::{Ready, ready},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Request header carrying the caller's deadline in unix milliseconds
+pub const REQUEST_DEADLINE_HEADER: &str = "x-request-deadline";
+
+/// Point in time after which the caller no longer wants a response
+///
+/// Usable as a handler argument; requests without a (valid) deadline header never expire.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Deadline(Option<SystemTime>);
+
+impl Deadline {
+    /// Parse a header value holding unix milliseconds; malformed values, and times too far
+    /// out for `SystemTime`, mean no deadline
+    pub fn from_header(value: Option<&HeaderValue>) -> Self {
+        let at = value
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .and_then(|millis| UNIX_EPOCH.checked_add(Duration::from_millis(millis)));
+        Self(at)
+    }
+
+    /// Time left before the deadline, zero once it has passed; `None` without a deadline
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0.map(|at| {
+            at.duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO)
+        })
+    }
+
+    /// Fail with a 504 if the deadline has passed
+    ///
+    /// Marks the current span with `deadline.exceeded`; the request span gets the same field
+    /// from `RequestIdMiddleware` when the error is rendered.
+    pub fn check(&self, request_id: &RequestId) -> Result<(), ApiError> {
+        if self.remaining() != Some(Duration::ZERO) {
+            return Ok(());
+        }
+
+        tracing::Span::current().set_attribute("deadline.exceeded", true);
+        warn!("Request deadline exceeded, abandoning work");
+
+        Err(ApiError::new(
+            ErrorKind::DeadlineExceeded,
+            "request deadline exceeded",
+            request_id,
+        ))
+    }
+}
+
+impl FromRequest for Deadline {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .extensions()
+            .get::<Self>()
+            .copied()
+            .unwrap_or_default()))
+    }
+}
@@ -0,0 +1,331 @@
+/// Structured API errors
+///
+/// This module shows how to:
+/// - Return every error with the same JSON shape: `{"error": {"code", "message", "request_id"}}`
+/// - Map error kinds to HTTP status codes in one place
+/// - Correlate an error body with its logs and trace via the request ID
+/// - List per-field validation failures in `details`
+use actix_web::{
+    FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError, dev::Payload,
+    error::JsonPayloadError, http::StatusCode,
+};
+use serde::Serialize;
+use std::{
+    convert::Infallible,
+    fmt,
+    future::{Ready, ready},
+};
+
+use crate::validation::FieldError;
+
+/// Request ID assigned by `RequestIdMiddleware`, stored in request extensions
+///
+/// Usable as a handler argument; resolves to an empty ID when the middleware is not mounted.
+#[derive(Clone, Debug, Default)]
+pub struct RequestId(pub String);
+
+impl FromRequest for RequestId {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .extensions()
+            .get::<Self>()
+            .cloned()
+            .unwrap_or_default()))
+    }
+}
+
+/// Category of an API error, which determines the status code and `code` field
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    Validation,
+    BadJson,
+    NotFound,
+    PayloadTooLarge,
+    DeadlineExceeded,
+    Timeout,
+    RateLimited,
+    ShuttingDown,
+    Internal,
+}
+
+impl ErrorKind {
+    /// Stable machine-readable identifier sent as `error.code`
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Validation => "validation_error",
+            Self::BadJson => "bad_json",
+            Self::NotFound => "not_found",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::DeadlineExceeded => "deadline_exceeded",
+            Self::Timeout => "timeout",
+            Self::RateLimited => "rate_limited",
+            Self::ShuttingDown => "shutting_down",
+            Self::Internal => "internal_error",
+        }
+    }
+
+    /// Value of the `kind` label on `app_errors_total`
+    pub fn metric_label(self) -> &'static str {
+        match self {
+            Self::Validation => "validation",
+            Self::BadJson => "bad_json",
+            Self::NotFound => "not_found",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::DeadlineExceeded => "deadline_exceeded",
+            Self::Timeout => "timeout",
+            Self::RateLimited => "rate_limited",
+            Self::ShuttingDown => "shutting_down",
+            Self::Internal => "internal",
+        }
+    }
+
+    pub fn status_code(self) -> StatusCode {
+        match self {
+            Self::Validation | Self::BadJson => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+            Self::Timeout | Self::ShuttingDown => StatusCode::SERVICE_UNAVAILABLE,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Error returned by handlers, rendered as a JSON body with the matching status code
+#[derive(Debug)]
+pub struct ApiError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub request_id: String,
+    /// Per-field validation failures, rendered as `error.details` when present
+    pub details: Vec<FieldError>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail<'a> {
+    code: &'static str,
+    message: &'a str,
+    request_id: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    details: &'a [FieldError],
+}
+
+impl ApiError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>, request_id: &RequestId) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            request_id: request_id.0.clone(),
+            details: Vec::new(),
+        }
+    }
+
+    pub fn validation(message: impl Into<String>, request_id: &RequestId) -> Self {
+        Self::new(ErrorKind::Validation, message, request_id)
+    }
+
+    /// Validation failure listing every invalid field
+    pub fn invalid_fields(details: Vec<FieldError>, request_id: &RequestId) -> Self {
+        let summary = details
+            .iter()
+            .map(|error| format!("{}: {}", error.field, error.message))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        Self {
+            details,
+            ..Self::validation(format!("Invalid request: {summary}"), request_id)
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>, request_id: &RequestId) -> Self {
+        Self::new(ErrorKind::NotFound, message, request_id)
+    }
+
+    pub fn internal(message: impl Into<String>, request_id: &RequestId) -> Self {
+        Self::new(ErrorKind::Internal, message, request_id)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.code(), self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.kind.status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: ErrorDetail {
+                code: self.kind.code(),
+                message: &self.message,
+                request_id: &self.request_id,
+                details: &self.details,
+            },
+        })
+    }
+}
+
+/// `JsonConfig` error handler: oversized bodies become a structured 413 and malformed JSON a
+/// structured 400
+///
+/// Other failures (e.g. a wrong content type) keep actix's default response.
+pub fn json_error_handler(err: JsonPayloadError, req: &HttpRequest) -> actix_web::Error {
+    let request_id = || {
+        req.extensions()
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    match err {
+        JsonPayloadError::Overflow { limit }
+        | JsonPayloadError::OverflowKnownLength { limit, .. } => {
+            tracing::warn!(name: "body_too_large", limit, "Rejected oversized JSON body");
+
+            ApiError::new(ErrorKind::PayloadTooLarge, err.to_string(), &request_id()).into()
+        }
+        JsonPayloadError::Deserialize(ref source) => {
+            tracing::warn!(
+                name: "deserialization.error",
+                error = %source,
+                deserialization.category = ?source.classify(),
+                deserialization.line = source.line(),
+                deserialization.column = source.column(),
+                "Rejected malformed JSON body"
+            );
+
+            ApiError::new(ErrorKind::BadJson, err.to_string(), &request_id()).into()
+        }
+        err => err.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, test as actix_test, web};
+
+    async fn body(err: &ApiError) -> serde_json::Value {
+        let body = actix_web::body::to_bytes(err.error_response().into_body())
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn not_found_and_validation_share_one_shape() {
+        let request_id = RequestId("req-1".to_string());
+
+        let not_found = ApiError::not_found("User not found: 7", &request_id);
+        assert_eq!(not_found.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body(&not_found).await,
+            serde_json::json!({
+                "error": {
+                    "code": "not_found",
+                    "message": "User not found: 7",
+                    "request_id": "req-1",
+                }
+            })
+        );
+
+        let invalid = ApiError::invalid_fields(
+            vec![FieldError::new("email", "email must contain '@'")],
+            &request_id,
+        );
+        assert_eq!(invalid.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body(&invalid).await,
+            serde_json::json!({
+                "error": {
+                    "code": "validation_error",
+                    "message": "Invalid request: email: email must contain '@'",
+                    "request_id": "req-1",
+                    "details": [{ "field": "email", "message": "email must contain '@'" }],
+                }
+            })
+        );
+    }
+
+    async fn accept_json(body: web::Json<serde_json::Value>) -> HttpResponse {
+        HttpResponse::Ok().json(body.into_inner())
+    }
+
+    #[actix_web::test]
+    async fn oversized_json_body_is_payload_too_large() {
+        // Error responses bump the global `app_errors_total`
+        let _metrics = crate::test_support::lock_metrics().await;
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(
+                    web::JsonConfig::default()
+                        .limit(32)
+                        .error_handler(json_error_handler),
+                )
+                .route("/api/users", web::post().to(accept_json)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/users")
+            .set_json(serde_json::json!({ "name": "x".repeat(64) }))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: serde_json::Value = actix_test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "payload_too_large");
+    }
+
+    #[actix_web::test]
+    async fn malformed_json_is_counted_as_bad_json() {
+        let _metrics = crate::test_support::lock_metrics().await;
+        let tracing = crate::test_support::TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(crate::custom_middleware::RequestIdMiddleware::new(
+                    actix_web::http::header::HeaderName::from_static("x-request-id"),
+                    std::time::Duration::from_secs(60),
+                ))
+                .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+                .route("/api/users", web::post().to(accept_json)),
+        )
+        .await;
+        let bad_json =
+            || crate::test_support::metric_value("app_errors_total", &[("kind", "bad_json")]);
+        let before = bad_json();
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/users")
+            .insert_header(("content-type", "application/json"))
+            .set_payload(r#"{"name": "Carol","#)
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = actix_test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "bad_json");
+        assert_eq!(bad_json(), before + 1.0);
+
+        let span = tracing.request_span("/api/users").unwrap();
+        assert!(
+            span.events
+                .iter()
+                .any(|event| event.name == "Rejected malformed JSON body")
+        );
+    }
+}
@@ -5,8 +5,25 @@
 /// - Add events and attributes to spans
 /// - Handle errors with proper tracing
 /// - Track performance metrics
-use actix_web::{HttpResponse, Result as ActixResult, error::ErrorInternalServerError, web};
+use actix_web::{
+    HttpRequest, HttpResponse,
+    http::StatusCode,
+    web::{self, Bytes},
+};
+use futures::stream;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::{
+    auth::AdminAuth,
+    deadline::Deadline,
+    error::{ApiError, ErrorKind, RequestId},
+    idempotency::{IDEMPOTENCY_KEY_HEADER, IdempotencyCache},
+    metrics, observability, propagation,
+    response_format::ResponseFormat,
+    store::UserStore,
+    validation::{FieldError, Validate, validate},
+};
 use tracing::{Instrument, debug, info, warn};
 use uuid::Uuid;
 
@@ -18,52 +35,358 @@ pub struct User {
     pub email: String,
 }
 
+/// Artificial delay for the simulated external dependency check
+const SIMULATED_EXTERNAL_LATENCY: Duration = Duration::from_millis(2);
+
 /// Health check endpoint - simplest instrumentation
 ///
 /// Demonstrates:
 /// - Basic span creation
 /// - Logging structured data
-pub async fn health_check() -> ActixResult<HttpResponse> {
+/// - Reporting each dependency separately, degrading the overall status if any fails
+///
+/// Always answers 200; `/readyz` is the probe that takes the instance out of rotation.
+pub async fn health_check(store: Option<web::Data<UserStore>>) -> Result<HttpResponse, ApiError> {
     let span = tracing::info_span!("health_check");
 
     async {
         info!("Health check requested");
+
+        let checks = [
+            ("telemetry", observability::is_telemetry_ready()),
+            ("user_store", store.is_some()),
+            ("external_service", check_external_service().await),
+        ];
+        let healthy = checks.iter().all(|(_, ok)| *ok);
+
+        if !healthy {
+            let failing: Vec<&str> = checks
+                .iter()
+                .filter(|(_, ok)| !ok)
+                .map(|(name, _)| *name)
+                .collect();
+            warn!(failing = ?failing, "Health check degraded");
+        }
+
+        let checks: serde_json::Map<String, serde_json::Value> = checks
+            .iter()
+            .map(|(name, ok)| {
+                (
+                    name.to_string(),
+                    (if *ok { "ok" } else { "failing" }).into(),
+                )
+            })
+            .collect();
+
         Ok(HttpResponse::Ok().json(serde_json::json!({
-            "status": "healthy",
-            "version": env!("CARGO_PKG_VERSION")
+            "status": if healthy { "healthy" } else { "degraded" },
+            "version": env!("CARGO_PKG_VERSION"),
+            "checks": checks,
         })))
     }
     .instrument(span)
     .await
 }
 
+/// Placeholder for a downstream service check; replace with a real call (e.g. a ping
+/// request) when the app gains one
+#[tracing::instrument(name = "health.external_service")]
+async fn check_external_service() -> bool {
+    tokio::time::sleep(SIMULATED_EXTERNAL_LATENCY).await;
+    true
+}
+
+/// Default page size for the user listing endpoints
+const DEFAULT_PAGE_LIMIT: usize = 20;
+
+/// Upper bound on the page size a client may request
+const MAX_PAGE_LIMIT: usize = 100;
+
+/// Artificial delay for the simulated database query, so its span has a visible duration
+const SIMULATED_DB_LATENCY: Duration = Duration::from_millis(5);
+
+/// Pagination parameters for `list_users`
+#[derive(Debug, Deserialize)]
+pub struct ListUsersQuery {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Paginated response envelope
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// Liveness probe - 200 whenever the process can serve requests
+pub async fn liveness() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "alive" }))
+}
+
+/// Inputs to the readiness decision
+#[derive(Clone, Copy, Debug)]
+struct ReadinessChecks {
+    telemetry: bool,
+    user_store: bool,
+    collector: bool,
+    collector_required: bool,
+}
+
+impl ReadinessChecks {
+    /// Ready once telemetry and the store are up; the collector counts only when required
+    fn is_ready(self) -> bool {
+        self.telemetry && self.user_store && (self.collector || !self.collector_required)
+    }
+
+    /// `ready`, `degraded` (serving while the collector is down) or `not_ready`
+    fn status(self) -> &'static str {
+        match (self.is_ready(), self.collector) {
+            (false, _) => "not_ready",
+            (true, false) => "degraded",
+            (true, true) => "ready",
+        }
+    }
+}
+
+/// Readiness probe - 200 only once telemetry and the user store are initialized
+///
+/// Demonstrates:
+/// - Separating "process is up" from "able to do useful work" for Kubernetes
+/// - Reporting individual checks in the response body
+/// - Degrading instead of failing when an optional dependency (the collector) is down
+#[tracing::instrument(skip(store))]
+pub async fn readiness(store: Option<web::Data<UserStore>>) -> HttpResponse {
+    let checks = ReadinessChecks {
+        telemetry: observability::is_telemetry_ready(),
+        user_store: store.is_some(),
+        collector: observability::is_collector_reachable(),
+        collector_required: observability::is_collector_required(),
+    };
+
+    let body = serde_json::json!({
+        "status": checks.status(),
+        "checks": {
+            "telemetry": checks.telemetry,
+            "user_store": checks.user_store,
+            "otlp_collector": checks.collector,
+        },
+    });
+
+    if checks.is_ready() {
+        HttpResponse::Ok().json(body)
+    } else {
+        warn!(
+            telemetry = checks.telemetry,
+            user_store = checks.user_store,
+            collector = checks.collector,
+            collector_required = checks.collector_required,
+            "Readiness check failed"
+        );
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
 /// List all users - demonstrates span attributes
 ///
 /// Demonstrates:
 /// - Creating spans with attributes
 /// - Nested spans
 /// - Error handling in spans
-#[tracing::instrument]
-pub async fn list_users() -> ActixResult<HttpResponse> {
+#[tracing::instrument(
+    skip(query, store, request_id, format),
+    fields(limit = tracing::field::Empty, offset = tracing::field::Empty)
+)]
+pub async fn list_users(
+    query: web::Query<ListUsersQuery>,
+    store: web::Data<UserStore>,
+    request_id: RequestId,
+    format: ResponseFormat,
+) -> Result<HttpResponse, ApiError> {
     info!("Listing all users");
 
-    // Simulate database query
-    let users = vec![
-        User {
-            id: Uuid::new_v4().to_string(),
-            name: "Alice".to_string(),
-            email: "alice@example.com".to_string(),
-        },
-        User {
-            id: Uuid::new_v4().to_string(),
-            name: "Bob".to_string(),
-            email: "bob@example.com".to_string(),
+    let users = query_users(&store).await;
+    let page = paginate(users, query.limit, query.offset);
+
+    info!(
+        count = page.items.len(),
+        total = page.total,
+        "Users retrieved"
+    );
+
+    encoded_response(StatusCode::OK, &page, format, &request_id)
+}
+
+/// Export all users as NDJSON - demonstrates instrumenting a streaming response
+///
+/// Demonstrates:
+/// - Streaming one JSON document per line instead of buffering the whole dataset
+/// - Keeping the handler span open until the stream ends, so `records` is recorded last
+/// - Logging against a span explicitly (`parent:`) from code polled outside the handler
+#[tracing::instrument(skip(store), fields(records = tracing::field::Empty))]
+pub async fn export_users(store: web::Data<UserStore>) -> HttpResponse {
+    let users = query_users(&store).await;
+    info!(total = users.len(), "Streaming user export");
+
+    let span = tracing::Span::current();
+    let lines = stream::unfold(
+        (users.into_iter(), 0usize, span),
+        |(mut users, streamed, span)| async move {
+            let Some(user) = users.next() else {
+                span.record("records", streamed);
+                info!(parent: &span, records = streamed, "User export finished");
+                return None;
+            };
+
+            let line = serde_json::to_vec(&user)
+                .map(|mut line| {
+                    line.push(b'\n');
+                    Bytes::from(line)
+                })
+                .map_err(actix_web::error::ErrorInternalServerError);
+
+            Some((line, (users, streamed + 1, span)))
         },
-    ];
+    );
+
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(lines)
+}
 
-    info!(count = users.len(), "Users retrieved");
+/// Filters for `search_users`; omitted filters match every user
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    pub name_contains: Option<String>,
+    pub email_domain: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl SearchParams {
+    /// Case-insensitive name substring and exact email domain match
+    fn matches(&self, user: &User) -> bool {
+        let name_matches = self
+            .name_contains
+            .as_deref()
+            .is_none_or(|needle| user.name.to_lowercase().contains(&needle.to_lowercase()));
+
+        let domain_matches = self.email_domain.as_deref().is_none_or(|domain| {
+            user.email
+                .rsplit_once('@')
+                .is_some_and(|(_, user_domain)| user_domain.eq_ignore_ascii_case(domain))
+        });
 
-    Ok(HttpResponse::Ok().json(users))
+        name_matches && domain_matches
+    }
+}
+
+/// Search users - demonstrates recording query predicates and outcomes on a span
+///
+/// Demonstrates:
+/// - Optional filters as span fields (empty when not supplied)
+/// - Recording the match count after the work is done
+#[tracing::instrument(
+    skip(query, store, request_id),
+    fields(
+        name_contains = query.name_contains.as_deref(),
+        email_domain = query.email_domain.as_deref(),
+        matches = tracing::field::Empty,
+        limit = tracing::field::Empty,
+        offset = tracing::field::Empty,
+    )
+)]
+pub async fn search_users(
+    query: web::Query<SearchParams>,
+    store: web::Data<UserStore>,
+    request_id: RequestId,
+    format: ResponseFormat,
+) -> Result<HttpResponse, ApiError> {
+    info!("Searching users");
+
+    let users: Vec<User> = query_users(&store)
+        .await
+        .into_iter()
+        .filter(|user| query.matches(user))
+        .collect();
+    tracing::Span::current().record("matches", users.len());
+
+    let page = paginate(users, query.limit, query.offset);
+
+    info!(
+        count = page.items.len(),
+        total = page.total,
+        "Users matched"
+    );
+
+    encoded_response(StatusCode::OK, &page, format, &request_id)
+}
+
+/// Read all users inside a simulated database query span so the nesting shows up in traces
+async fn query_users(store: &UserStore) -> Vec<User> {
+    async {
+        tokio::time::sleep(SIMULATED_DB_LATENCY).await;
+        store.list()
+    }
+    .instrument(tracing::info_span!(
+        "db.query",
+        db.system = "memory",
+        db.operation = "select"
+    ))
+    .await
+}
+
+/// Response in the negotiated format, encoded in a `serialize` child span
+///
+/// Encoding large bodies takes real time; doing it here rather than in `HttpResponse::json`
+/// makes that time, the chosen `format` and the body size (`bytes`) visible in the trace.
+fn encoded_response<T: Serialize>(
+    status: StatusCode,
+    value: &T,
+    format: ResponseFormat,
+    request_id: &RequestId,
+) -> Result<HttpResponse, ApiError> {
+    let span = tracing::info_span!(
+        "serialize",
+        format = format.label(),
+        bytes = tracing::field::Empty
+    );
+
+    let body = span.in_scope(|| {
+        format.encode(value).map_err(|err| {
+            warn!(error = %err, "Failed to serialize response");
+            ApiError::internal(format!("failed to serialize response: {err}"), request_id)
+        })
+    })?;
+    span.record("bytes", body.len());
+
+    Ok(HttpResponse::build(status)
+        .content_type(format.content_type())
+        .body(body))
+}
+
+/// Slice one page out of `items`, applying the default and maximum page sizes
+///
+/// The resolved `limit` and `offset` are recorded on the current span when it declares them.
+fn paginate<T>(items: Vec<T>, limit: Option<usize>, offset: Option<usize>) -> Page<T> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+    let offset = offset.unwrap_or(0);
+
+    let span = tracing::Span::current();
+    span.record("limit", limit);
+    span.record("offset", offset);
+
+    let total = items.len();
+    let items = items.into_iter().skip(offset).take(limit).collect();
+
+    Page {
+        items,
+        total,
+        limit,
+        offset,
+    }
 }
 
 /// Create a new user - demonstrates request parsing and validation
@@ -78,25 +401,290 @@ pub struct CreateUserRequest {
     pub email: String,
 }
 
-#[tracing::instrument(skip(req))]
-pub async fn create_user(req: web::Json<CreateUserRequest>) -> ActixResult<HttpResponse> {
+/// Longest accepted user name, in characters
+const MAX_NAME_LEN: usize = 100;
+
+impl Validate for CreateUserRequest {
+    const FIELDS: &'static [&'static str] = &["name", "email"];
+
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new("name", "name must not be blank"));
+        } else if self.name.chars().count() > MAX_NAME_LEN {
+            errors.push(FieldError::new(
+                "name",
+                format!("name must be at most {MAX_NAME_LEN} characters"),
+            ));
+        }
+
+        if let Err(reason) = validate_email(&self.email) {
+            errors.push(FieldError::new("email", reason));
+        }
+
+        errors
+    }
+}
+
+#[tracing::instrument(
+    skip(http_req, req, store, created_users, request_id, format),
+    fields(idempotency.hit = tracing::field::Empty)
+)]
+pub async fn create_user(
+    http_req: HttpRequest,
+    req: web::Json<CreateUserRequest>,
+    store: web::Data<UserStore>,
+    created_users: web::Data<IdempotencyCache<User>>,
+    request_id: RequestId,
+    format: ResponseFormat,
+) -> Result<HttpResponse, ApiError> {
     info!("Creating new user: {}", req.name);
 
-    // Validate email (simple validation)
-    if !req.email.contains('@') {
-        warn!("Invalid email format provided");
-        return Err(ErrorInternalServerError("Invalid email format"));
+    let create = || {
+        validate(&*req, &request_id)?;
+
+        let user = User {
+            id: Uuid::new_v4().to_string(),
+            name: req.name.clone(),
+            email: req.email.clone(),
+        };
+
+        let store_size = store.insert(user.clone());
+        metrics::set_users_total(store_size);
+
+        info!(user_id = %user.id, store_size, "User created successfully");
+        Ok(user)
+    };
+
+    // A retry with a key seen within the TTL gets the original user back
+    let idempotency_key = http_req
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    let user = match idempotency_key {
+        Some(key) => {
+            let (user, hit) = created_users.get_or_try_insert_with(key, create)?;
+            tracing::Span::current().record("idempotency.hit", hit);
+            if hit {
+                info!(user_id = %user.id, "Replaying user created for idempotency key");
+            }
+            user
+        }
+        None => create()?,
+    };
+
+    encoded_response(StatusCode::CREATED, &user, format, &request_id)
+}
+
+/// Largest number of items accepted by one batch request
+const MAX_BATCH_SIZE: usize = 100;
+
+/// Outcome of one entry in a batch create, in request order
+#[derive(Debug, Serialize)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<BatchItemError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchItemError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Create several users at once - demonstrates aggregate counts on one span
+///
+/// Each entry is validated on its own, so invalid entries are reported without aborting the
+/// rest. The response is always 207 Multi-Status with a per-item status.
+#[tracing::instrument(
+    skip(req, store, request_id),
+    fields(
+        total = req.len(),
+        succeeded = tracing::field::Empty,
+        failed = tracing::field::Empty
+    )
+)]
+pub async fn create_users_batch(
+    req: web::Json<Vec<CreateUserRequest>>,
+    store: web::Data<UserStore>,
+    request_id: RequestId,
+) -> Result<HttpResponse, ApiError> {
+    if req.len() > MAX_BATCH_SIZE {
+        warn!("Batch too large");
+        return Err(ApiError::validation(
+            format!(
+                "batch too large: {} users (max {MAX_BATCH_SIZE})",
+                req.len()
+            ),
+            &request_id,
+        ));
+    }
+
+    info!("Creating {} users in a batch", req.len());
+
+    let results: Vec<BatchItemResult> = req
+        .iter()
+        .enumerate()
+        .map(|(index, item)| match validate(item, &request_id) {
+            Ok(()) => {
+                let user = User {
+                    id: Uuid::new_v4().to_string(),
+                    name: item.name.clone(),
+                    email: item.email.clone(),
+                };
+                store.insert(user.clone());
+
+                BatchItemResult {
+                    index,
+                    status: StatusCode::CREATED.as_u16(),
+                    id: Some(user.id),
+                    error: None,
+                }
+            }
+            Err(err) => {
+                metrics::track_app_error(err.kind.metric_label());
+
+                BatchItemResult {
+                    index,
+                    status: err.kind.status_code().as_u16(),
+                    id: None,
+                    error: Some(BatchItemError {
+                        code: err.kind.code(),
+                        message: err.message,
+                    }),
+                }
+            }
+        })
+        .collect();
+
+    let succeeded = results.iter().filter(|result| result.id.is_some()).count();
+    let failed = results.len() - succeeded;
+    metrics::set_users_total(store.len());
+
+    let span = tracing::Span::current();
+    span.record("succeeded", succeeded);
+    span.record("failed", failed);
+    info!(succeeded, failed, "Batch create finished");
+
+    Ok(HttpResponse::MultiStatus().json(serde_json::json!({
+        "total": results.len(),
+        "succeeded": succeeded,
+        "failed": failed,
+        "results": results,
+    })))
+}
+
+/// Check that an email has a local part, a single `@`, and a dotted domain
+pub fn validate_email(email: &str) -> Result<(), &'static str> {
+    if email.chars().any(char::is_whitespace) {
+        return Err("email must not contain whitespace");
     }
 
-    let user = User {
-        id: Uuid::new_v4().to_string(),
-        name: req.name.clone(),
-        email: req.email.clone(),
+    let Some((local, domain)) = email.split_once('@') else {
+        return Err("email must contain '@'");
     };
 
-    info!(user_id = %user.id, "User created successfully");
+    if local.is_empty() {
+        return Err("email local part is empty");
+    }
+
+    if domain.contains('@') {
+        return Err("email must contain exactly one '@'");
+    }
+
+    if !domain.contains('.') {
+        return Err("email domain must contain a dot");
+    }
 
-    Ok(HttpResponse::Created().json(user))
+    if domain.split('.').any(str::is_empty) {
+        return Err("email domain has an empty label");
+    }
+
+    Ok(())
+}
+
+/// Current trace context - demonstrates reading OTEL IDs and baggage from the active span
+///
+/// Returns the `trace_id`/`span_id` of this handler's span, or nulls when no OTEL layer is
+/// exporting spans, plus the W3C baggage the caller sent. Useful for checking that a
+/// `traceparent` (and `baggage`) sent by a caller was continued.
+#[tracing::instrument]
+pub async fn trace_context() -> Result<HttpResponse, ApiError> {
+    let span = tracing::Span::current();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "trace_id": propagation::trace_id(&span),
+        "span_id": propagation::span_id(&span),
+        "baggage": propagation::current_baggage(),
+    })))
+}
+
+/// Subtasks spawned by `linked_tasks`
+const LINKED_TASK_COUNT: u64 = 2;
+
+/// Run concurrent subtasks in traces of their own - demonstrates span links
+///
+/// Demonstrates:
+/// - Starting root spans (`parent: None`) for fanned-out work instead of child spans
+/// - Linking each subtask span back to the request span
+/// - Spawning and joining tasks with the span attached via `Instrument`
+#[tracing::instrument(skip(request_id), fields(links.count = tracing::field::Empty))]
+pub async fn linked_tasks(request_id: RequestId) -> Result<HttpResponse, ApiError> {
+    let request_span = tracing::Span::current();
+    let mut links = 0;
+
+    let handles: Vec<_> = (0..LINKED_TASK_COUNT)
+        .map(|task| {
+            let span = tracing::info_span!(parent: None, "linked_task", task);
+            if propagation::add_link(&span, &request_span) {
+                links += 1;
+            }
+
+            let trace_id = propagation::trace_id(&span);
+            let handle = actix_web::rt::spawn(run_linked_task(task).instrument(span));
+            (task, trace_id, handle)
+        })
+        .collect();
+
+    // The tasks already run concurrently; awaiting in order only collects their results
+    let mut tasks = Vec::with_capacity(handles.len());
+    for (task, trace_id, handle) in handles {
+        let duration_ms = handle
+            .await
+            .map_err(|err| ApiError::internal(err.to_string(), &request_id))?;
+
+        tasks.push(serde_json::json!({
+            "task": task,
+            "trace_id": trace_id,
+            "duration_ms": duration_ms,
+        }));
+    }
+
+    request_span.record("links.count", links);
+    info!(links, "Linked subtasks finished");
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "trace_id": propagation::trace_id(&request_span),
+        "tasks": tasks,
+    })))
+}
+
+/// Simulated work for one linked subtask; later tasks take longer so the spans are distinct
+async fn run_linked_task(task: u64) -> u64 {
+    let start = Instant::now();
+    debug!("Linked subtask started");
+
+    tokio::time::sleep(Duration::from_millis(20 * (task + 1))).await;
+
+    let duration_ms = start.elapsed().as_millis() as u64;
+    debug!(duration_ms, "Linked subtask finished");
+    duration_ms
 }
 
 /// Get a specific user - demonstrates error handling in spans
@@ -105,26 +693,136 @@ pub async fn create_user(req: web::Json<CreateUserRequest>) -> ActixResult<HttpR
 /// - Path parameters in spans
 /// - Error cases with context
 /// - Using nested spans
-#[tracing::instrument(skip(path))]
-pub async fn get_user(path: web::Path<String>) -> ActixResult<HttpResponse> {
+#[tracing::instrument(skip(path, store, request_id, format))]
+pub async fn get_user(
+    path: web::Path<String>,
+    store: web::Data<UserStore>,
+    request_id: RequestId,
+    format: ResponseFormat,
+) -> Result<HttpResponse, ApiError> {
     let user_id = path.into_inner();
 
     info!(user_id = %user_id, "Fetching user by ID");
 
     // Simulate database lookup
-    if user_id == "404" {
+    let Some(user) = store.get(&user_id) else {
+        warn!(store_size = store.len(), "User not found");
+        return Err(user_not_found(&user_id, &request_id));
+    };
+
+    info!(store_size = store.len(), "User found");
+    encoded_response(StatusCode::OK, &user, format, &request_id)
+}
+
+/// Side length of generated avatars, in pixels
+const AVATAR_SIZE: u32 = 128;
+
+/// Get a user's avatar - demonstrates instrumenting a non-JSON response
+///
+/// Demonstrates:
+/// - Returning binary content with an explicit content type
+/// - Recording the generated byte count on a child span
+#[tracing::instrument(skip(path, store, request_id), fields(user_id = %path))]
+pub async fn get_user_avatar(
+    path: web::Path<String>,
+    store: web::Data<UserStore>,
+    request_id: RequestId,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+
+    let Some(user) = store.get(&user_id) else {
         warn!("User not found");
-        return Err(ErrorInternalServerError("User not found"));
-    }
+        return Err(user_not_found(&user_id, &request_id));
+    };
+
+    let span = tracing::info_span!(
+        "render_avatar",
+        format = "svg",
+        bytes = tracing::field::Empty
+    );
+    let body = span.in_scope(|| render_avatar(&user));
+    span.record("bytes", body.len());
+
+    Ok(HttpResponse::Ok().content_type("image/svg+xml").body(body))
+}
+
+/// Render a deterministic SVG avatar: the name's initial on a background derived from the ID
+fn render_avatar(user: &User) -> String {
+    let hue = user.id.bytes().fold(0u32, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte as u32)
+    }) % 360;
+    // Alphanumeric only, so the initial never needs XML escaping
+    let initial = user
+        .name
+        .chars()
+        .find(|c| c.is_alphanumeric())
+        .map_or('?', |c| c.to_uppercase().next().unwrap_or(c));
+    let half = AVATAR_SIZE / 2;
+
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{AVATAR_SIZE}" height="{AVATAR_SIZE}" viewBox="0 0 {AVATAR_SIZE} {AVATAR_SIZE}"><rect width="{AVATAR_SIZE}" height="{AVATAR_SIZE}" fill="hsl({hue},55%,50%)"/><text x="{half}" y="{half}" dy=".35em" text-anchor="middle" font-family="sans-serif" font-size="{half}" fill="white">{initial}</text></svg>"#
+    )
+}
+
+/// Update a user - demonstrates full replacement with shared validation
+///
+/// Demonstrates:
+/// - Recording the target ID as a span field
+/// - Reusing validation across handlers
+#[tracing::instrument(skip(path, req, store, request_id, format), fields(user_id = %path))]
+pub async fn update_user(
+    path: web::Path<String>,
+    req: web::Json<CreateUserRequest>,
+    store: web::Data<UserStore>,
+    request_id: RequestId,
+    format: ResponseFormat,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+    info!("Updating user");
+
+    validate(&*req, &request_id)?;
 
-    let user = User {
-        id: user_id.clone(),
-        name: "John Doe".to_string(),
-        email: "john@example.com".to_string(),
+    let CreateUserRequest { name, email } = req.into_inner();
+
+    let Some(user) = store.update(&user_id, name, email) else {
+        warn!("User not found");
+        return Err(user_not_found(&user_id, &request_id));
     };
 
-    info!("User found");
-    Ok(HttpResponse::Ok().json(user))
+    info!("User updated successfully");
+    encoded_response(StatusCode::OK, &user, format, &request_id)
+}
+
+/// Delete a user - demonstrates recording outcomes on mutating operations
+///
+/// Demonstrates:
+/// - Distinguishing success from not-found in events
+/// - Returning empty responses (204)
+#[tracing::instrument(skip(path, store, request_id))]
+pub async fn delete_user(
+    path: web::Path<String>,
+    store: web::Data<UserStore>,
+    request_id: RequestId,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+
+    info!(user_id = %user_id, "Deleting user by ID");
+
+    if store.remove(&user_id).is_none() {
+        warn!(user_id = %user_id, "User not found");
+        return Err(user_not_found(&user_id, &request_id));
+    }
+
+    let store_size = store.len();
+    metrics::set_users_total(store_size);
+
+    info!(user_id = %user_id, store_size, "User deleted");
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Shared 404 for the single-user handlers
+fn user_not_found(user_id: &str, request_id: &RequestId) -> ApiError {
+    ApiError::not_found(format!("User not found: {user_id}"), request_id)
 }
 
 /// Compute fibonacci - demonstrates CPU-intensive work with nested spans
@@ -139,32 +837,1500 @@ pub struct FibonacciRequest {
     pub n: u32,
 }
 
-#[tracing::instrument(skip(req))]
-pub async fn compute_fibonacci(req: web::Json<FibonacciRequest>) -> ActixResult<HttpResponse> {
-    if req.n > 50 {
-        warn!("Fibonacci computation requested with large n: {}", req.n);
-        return Err(ErrorInternalServerError("n too large (max 50)"));
+impl FibonacciRequest {
+    /// Whether fib(n) is out of `u64` range
+    fn overflows(&self) -> bool {
+        self.n > MAX_FIBONACCI_N
     }
+}
+
+impl Validate for FibonacciRequest {
+    const FIELDS: &'static [&'static str] = &["n"];
+
+    fn validate(&self) -> Vec<FieldError> {
+        if self.overflows() {
+            return vec![FieldError::new(
+                "n",
+                format!("fib({}) overflows u64 (max {MAX_FIBONACCI_N})", self.n),
+            )];
+        }
+
+        Vec::new()
+    }
+}
+
+#[tracing::instrument(
+    skip(req, request_id, deadline),
+    fields(n = req.n, overflow = req.overflows())
+)]
+pub async fn compute_fibonacci(
+    req: web::Json<FibonacciRequest>,
+    request_id: RequestId,
+    deadline: Deadline,
+) -> Result<HttpResponse, ApiError> {
+    deadline.check(&request_id)?;
+    validate(&*req, &request_id)?;
 
     info!("Computing fibonacci number for n={}", req.n);
 
-    let result = compute_fib_recursive(req.n);
+    Ok(fibonacci_response(req.n, timed_fib(req.n)))
+}
 
-    info!(result = result, "Fibonacci computation completed");
+/// Compute fibonacci on the blocking pool - demonstrates carrying a span across threads
+///
+/// Demonstrates:
+/// - Offloading CPU work so async workers stay responsive
+/// - Re-entering the request span inside the blocking closure
+#[tracing::instrument(
+    skip(req, request_id, deadline),
+    fields(n = req.n, overflow = req.overflows())
+)]
+pub async fn compute_fibonacci_async(
+    req: web::Json<FibonacciRequest>,
+    request_id: RequestId,
+    deadline: Deadline,
+) -> Result<HttpResponse, ApiError> {
+    deadline.check(&request_id)?;
+    validate(&*req, &request_id)?;
 
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "n": req.n,
-        "result": result,
+    let n = req.n;
+    let span = tracing::Span::current();
+    let dispatch = tracing::dispatcher::get_default(tracing::Dispatch::clone);
+
+    info!("Offloading fibonacci computation for n={n} to the blocking pool");
+
+    // The blocking thread has no current span, so enter ours explicitly to keep the work
+    // (and its events) under this request's trace. Its default subscriber is the global one,
+    // so carry ours over too in case it was only set for this thread (as in tests).
+    let result = web::block(move || {
+        tracing::dispatcher::with_default(&dispatch, || {
+            span.in_scope(|| {
+                debug!("Computing fibonacci on blocking thread");
+                timed_fib(n)
+            })
+        })
+    })
+    .await
+    .map_err(|err| ApiError::internal(err.to_string(), &request_id))?;
+
+    Ok(fibonacci_response(n, result))
+}
+
+/// Outcome of one entry in a batch computation, in request order
+#[derive(Debug, Serialize)]
+pub struct FibonacciBatchResult {
+    pub index: usize,
+    pub n: u32,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<BatchItemError>,
+}
+
+/// Compute several fibonacci numbers at once - demonstrates one child span per item
+///
+/// Each entry runs in its own `fibonacci` span under the request and is validated on its own,
+/// so out-of-range values (and entries reached after the deadline) are reported per item
+/// without failing the batch. The response is always 207 Multi-Status; `compute_us` sums the
+/// time spent computing across all entries.
+#[tracing::instrument(
+    skip(req, request_id, deadline),
+    fields(
+        total = req.len(),
+        succeeded = tracing::field::Empty,
+        failed = tracing::field::Empty,
+        compute_us = tracing::field::Empty
+    )
+)]
+pub async fn compute_fibonacci_batch(
+    req: web::Json<Vec<FibonacciRequest>>,
+    request_id: RequestId,
+    deadline: Deadline,
+) -> Result<HttpResponse, ApiError> {
+    deadline.check(&request_id)?;
+    if req.len() > MAX_BATCH_SIZE {
+        warn!("Batch too large");
+        return Err(ApiError::validation(
+            format!(
+                "batch too large: {} values (max {MAX_BATCH_SIZE})",
+                req.len()
+            ),
+            &request_id,
+        ));
+    }
+
+    info!("Computing {} fibonacci numbers in a batch", req.len());
+
+    let items = req.into_inner();
+    let span = tracing::Span::current();
+    let dispatch = tracing::dispatcher::get_default(tracing::Dispatch::clone);
+    let item_request_id = request_id.clone();
+
+    // A long batch would hold up this worker, so run it on the blocking pool under this
+    // request's span (see `compute_fibonacci_async`)
+    let (results, compute_time) = web::block(move || {
+        tracing::dispatcher::with_default(&dispatch, || {
+            span.in_scope(|| compute_batch_items(&items, deadline, &item_request_id))
+        })
+    })
+    .await
+    .map_err(|err| ApiError::internal(err.to_string(), &request_id))?;
+
+    let succeeded = results
+        .iter()
+        .filter(|result| result.result.is_some())
+        .count();
+    let failed = results.len() - succeeded;
+
+    let span = tracing::Span::current();
+    span.record("succeeded", succeeded);
+    span.record("failed", failed);
+    span.record("compute_us", compute_time.as_micros() as u64);
+    info!(succeeded, failed, "Batch computation finished");
+
+    Ok(HttpResponse::MultiStatus().json(serde_json::json!({
+        "total": results.len(),
+        "succeeded": succeeded,
+        "failed": failed,
+        "results": results,
     })))
 }
 
+/// Compute each batch entry in its own `fibonacci` span, returning the results and the total
+/// time spent computing
+///
+/// The deadline is checked before every entry, so once it passes the remaining entries are
+/// reported as 504s instead of being computed.
+fn compute_batch_items(
+    items: &[FibonacciRequest],
+    deadline: Deadline,
+    request_id: &RequestId,
+) -> (Vec<FibonacciBatchResult>, Duration) {
+    let mut compute_time = Duration::ZERO;
+    let results = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let span =
+                tracing::info_span!("fibonacci", index, n = item.n, overflow = item.overflows());
+            let _entered = span.enter();
+
+            let checked = deadline
+                .check(request_id)
+                .and_then(|()| validate(item, request_id));
+            if let Err(err) = checked {
+                metrics::track_app_error(err.kind.metric_label());
+
+                return FibonacciBatchResult {
+                    index,
+                    n: item.n,
+                    status: err.kind.status_code().as_u16(),
+                    result: None,
+                    error: Some(BatchItemError {
+                        code: err.kind.code(),
+                        message: err.message,
+                    }),
+                };
+            }
+
+            let start = Instant::now();
+            let result = Some(timed_fib(item.n));
+            compute_time += start.elapsed();
+
+            FibonacciBatchResult {
+                index,
+                n: item.n,
+                status: StatusCode::OK.as_u16(),
+                result,
+                error: None,
+            }
+        })
+        .collect();
+
+    (results, compute_time)
+}
+
+/// Shared response for the fibonacci handlers
+fn fibonacci_response(n: u32, result: u64) -> HttpResponse {
+    info!(
+        result = result,
+        iterations = n.saturating_sub(1),
+        "Fibonacci computation completed"
+    );
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "n": n,
+        "result": result,
+    }))
+}
+
+/// Run `fib_iterative`, observing only the computation in `fibonacci_computation_seconds`
+///
+/// Callers validate the request first, so `n` never exceeds `MAX_FIBONACCI_N`.
+fn timed_fib(n: u32) -> u64 {
+    let start = Instant::now();
+    let result = fib_iterative(n).expect("validated n is at most MAX_FIBONACCI_N");
+    metrics::track_fibonacci_computation(n, start.elapsed());
+    result
+}
+
+/// Largest n whose fibonacci number fits in a u64
+const MAX_FIBONACCI_N: u32 = 93;
+
+/// Iterative fibonacci - O(n) time, so large inputs stay cheap and produce no extra spans
+///
+/// Returns `None` when the result would overflow a u64 instead of wrapping.
+fn fib_iterative(n: u32) -> Option<u64> {
+    let (mut previous, mut current) = (0u64, 1u64);
+
+    if n == 0 {
+        return Some(previous);
+    }
+
+    for _ in 1..n {
+        let next = previous.checked_add(current)?;
+        previous = current;
+        current = next;
+    }
+
+    Some(current)
+}
+
+/// Recursion levels that get their own span; deeper calls run uninstrumented
+const FIB_SPAN_DEPTH: u32 = 2;
+
 /// Recursive fibonacci with instrumentation
-#[tracing::instrument(skip_all)]
+///
+/// Kept to illustrate per-call spans and as the oracle `fib_iterative` is tested against;
+/// O(2^n), so handlers use `fib_iterative` instead.
+/// Only the top `FIB_SPAN_DEPTH` levels are spanned (this call plus its two children), and
+/// the total call count is recorded as an event rather than one span per call.
+#[allow(dead_code)]
+#[tracing::instrument]
 fn compute_fib_recursive(n: u32) -> u64 {
-    if n <= 1 {
-        return n as u64;
+    let mut calls = 1u64;
+    let result = if n <= 1 {
+        n as u64
+    } else {
+        fib_recursive_at(n - 1, 1, &mut calls) + fib_recursive_at(n - 2, 1, &mut calls)
+    };
+
+    info!(fib.recursive_calls = calls, "Recursive fibonacci finished");
+    result
+}
+
+/// Recursive step; `depth` is the distance from `compute_fib_recursive`
+fn fib_recursive_at(n: u32, depth: u32, calls: &mut u64) -> u64 {
+    *calls += 1;
+
+    let recurse = |calls: &mut u64| {
+        if n <= 1 {
+            return n as u64;
+        }
+        fib_recursive_at(n - 1, depth + 1, calls) + fib_recursive_at(n - 2, depth + 1, calls)
+    };
+
+    if depth < FIB_SPAN_DEPTH {
+        tracing::info_span!("fib_recursive", n, depth).in_scope(|| recurse(calls))
+    } else {
+        recurse(calls)
+    }
+}
+
+/// Query for `GET /api/debug/error`
+#[derive(Debug, Deserialize)]
+pub struct DebugErrorQuery {
+    pub kind: String,
+}
+
+/// Fail on purpose with the requested error kind, for testing alerting pipelines
+///
+/// Demonstrates:
+/// - Each kind going through the normal error path, so `app_errors_total{kind}` and the
+///   status code match what a real failure would produce
+/// - Marking the handler span as an error regardless of status, so 4xx kinds show up too
+///
+/// Mounted only while `DEBUG_ENDPOINTS` is enabled (the default outside production).
+#[tracing::instrument(
+    skip(query, request_id),
+    fields(
+        error.kind = %query.kind,
+        otel.status_code = tracing::field::Empty,
+        otel.status_message = tracing::field::Empty
+    )
+)]
+pub async fn debug_error(
+    query: web::Query<DebugErrorQuery>,
+    request_id: RequestId,
+) -> Result<HttpResponse, ApiError> {
+    let kind = match query.kind.as_str() {
+        "validation" => ErrorKind::Validation,
+        "not_found" => ErrorKind::NotFound,
+        "internal" => ErrorKind::Internal,
+        "timeout" => ErrorKind::Timeout,
+        other => {
+            return Err(ApiError::validation(
+                format!(
+                    "Unknown error kind {other:?}, expected validation, not_found, internal or timeout"
+                ),
+                &request_id,
+            ));
+        }
+    };
+
+    let message = format!("Triggered {} error for testing", kind.metric_label());
+    let span = tracing::Span::current();
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_message", message.as_str());
+    warn!(error.code = kind.code(), "Returning requested test error");
+
+    Err(ApiError::new(kind, message, &request_id))
+}
+
+/// Request body for changing the log filter at runtime
+#[derive(Debug, Deserialize)]
+pub struct LogLevelRequest {
+    pub filter: String,
+}
+
+/// Change the log level without restarting - demonstrates runtime-reloadable filters
+///
+/// Demonstrates:
+/// - Turning on debug logging in production on demand
+/// - Rejecting invalid directives with a 400
+///
+/// Callers must send `Authorization: Bearer <ADMIN_TOKEN>`, or receive a 401 before the body
+/// is read.
+#[tracing::instrument(skip(_auth, req, request_id), fields(filter = tracing::field::Empty))]
+pub async fn update_log_level(
+    _auth: AdminAuth,
+    req: web::Json<LogLevelRequest>,
+    request_id: RequestId,
+) -> Result<HttpResponse, ApiError> {
+    tracing::Span::current().record("filter", req.filter.as_str());
+
+    if let Err(reason) = observability::set_log_filter(&req.filter) {
+        warn!(reason = %reason, "Rejected log filter update");
+        return Err(ApiError::validation(
+            format!("Invalid log filter: {reason}"),
+            &request_id,
+        ));
+    }
+
+    info!("Log filter updated");
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "filter": req.filter,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        TestTracing, attribute, event_attribute, lock_metrics, lock_metrics_blocking, metric_value,
+    };
+    use actix_web::{
+        App, Error,
+        body::MessageBody,
+        dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+        http::StatusCode,
+        test as actix_test,
+    };
+
+    /// The user routes as mounted in `main`, backed by `store`
+    fn users_app(
+        store: web::Data<UserStore>,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = Error,
+            InitError = (),
+        >,
+    > {
+        App::new()
+            .app_data(store)
+            .app_data(web::Data::new(IdempotencyCache::<User>::new(
+                Duration::from_secs(60),
+            )))
+            .route("/api/users", web::get().to(list_users))
+            .route("/api/users", web::post().to(create_user))
+            .route("/api/users/search", web::get().to(search_users))
+            .route("/api/users/batch", web::post().to(create_users_batch))
+            .route("/api/users/export", web::get().to(export_users))
+            .route("/api/users/{id}", web::get().to(get_user))
+            .route("/api/users/{id}", web::put().to(update_user))
+            .route("/api/users/{id}", web::delete().to(delete_user))
+            .route("/api/users/{id}/avatar", web::get().to(get_user_avatar))
+    }
+
+    #[actix_web::test]
+    async fn unknown_user_is_not_found() {
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/api/users/404")
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn invalid_email_is_bad_request() {
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/users")
+            .set_json(serde_json::json!({ "name": "Carol", "email": "carol@" }))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Create a user through the API, returning the stored record
+    async fn create(
+        app: &impl actix_web::dev::Service<
+            actix_http::Request,
+            Response = ServiceResponse<impl MessageBody>,
+            Error = Error,
+        >,
+        name: &str,
+        email: &str,
+    ) -> User {
+        let req = actix_test::TestRequest::post()
+            .uri("/api/users")
+            .set_json(serde_json::json!({ "name": name, "email": email }))
+            .to_request();
+        let res = actix_test::call_service(app, req).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        actix_test::read_body_json(res).await
+    }
+
+    #[actix_web::test]
+    async fn created_user_can_be_fetched() {
+        // Creating users moves the global `users_total` gauge
+        let _metrics = lock_metrics().await;
+        let store = web::Data::new(UserStore::default());
+        let app = actix_test::init_service(users_app(store.clone())).await;
+
+        let created = create(&app, "Carol", "carol@example.com").await;
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/api/users/{}", created.id))
+            .to_request();
+        let fetched: User = actix_test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(fetched.id, created.id);
+        assert_eq!(fetched.name, "Carol");
+        assert_eq!(fetched.email, "carol@example.com");
+        assert_eq!(store.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn deleted_user_is_gone() {
+        // Creating users moves the global `users_total` gauge
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+        let user = create(&app, "Dave", "dave@example.com").await;
+        let uri = format!("/api/users/{}", user.id);
+
+        let req = actix_test::TestRequest::delete().uri(&uri).to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        let req = actix_test::TestRequest::get().uri(&uri).to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let req = actix_test::TestRequest::delete().uri(&uri).to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn updated_fields_are_returned() {
+        // Creating users moves the global `users_total` gauge
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+        let user = create(&app, "Erin", "erin@example.com").await;
+        let uri = format!("/api/users/{}", user.id);
+
+        let req = actix_test::TestRequest::put()
+            .uri(&uri)
+            .set_json(serde_json::json!({ "name": "Erin B", "email": "erin@example.org" }))
+            .to_request();
+        let updated: User = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(updated.id, user.id);
+        assert_eq!(updated.name, "Erin B");
+        assert_eq!(updated.email, "erin@example.org");
+
+        let req = actix_test::TestRequest::get().uri(&uri).to_request();
+        let fetched: User = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(fetched.name, "Erin B");
+        assert_eq!(fetched.email, "erin@example.org");
+    }
+
+    #[actix_web::test]
+    async fn update_rejects_unknown_user_and_invalid_email() {
+        // Creating users moves the global `users_total` gauge
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+        let user = create(&app, "Frank", "frank@example.com").await;
+
+        let req = actix_test::TestRequest::put()
+            .uri("/api/users/missing")
+            .set_json(serde_json::json!({ "name": "Frank", "email": "frank@example.com" }))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let req = actix_test::TestRequest::put()
+            .uri(&format!("/api/users/{}", user.id))
+            .set_json(serde_json::json!({ "name": "Frank", "email": "frank" }))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn accepts_well_formed_emails() {
+        for email in [
+            "a@b.co",
+            "first.last@example.com",
+            "user+tag@mail.example.org",
+        ] {
+            assert_eq!(validate_email(email), Ok(()), "{email}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_emails() {
+        for (email, reason) in [
+            ("", "email must contain '@'"),
+            ("user.example.com", "email must contain '@'"),
+            ("@example.com", "email local part is empty"),
+            ("a@b@example.com", "email must contain exactly one '@'"),
+            ("user@localhost", "email domain must contain a dot"),
+            ("user@example.", "email domain has an empty label"),
+            ("user@.example.com", "email domain has an empty label"),
+            ("user name@example.com", "email must not contain whitespace"),
+        ] {
+            assert_eq!(validate_email(email), Err(reason), "{email:?}");
+        }
+    }
+
+    #[test]
+    fn iterative_fibonacci_matches_recursive() {
+        for n in 0..=20 {
+            assert_eq!(fib_iterative(n), Some(compute_fib_recursive(n)), "fib({n})");
+        }
+    }
+
+    #[test]
+    fn fibonacci_overflow_is_none() {
+        assert_eq!(
+            fib_iterative(MAX_FIBONACCI_N),
+            Some(12_200_160_415_121_876_738)
+        );
+        assert_eq!(fib_iterative(MAX_FIBONACCI_N + 1), None);
+        assert_eq!(fib_iterative(u32::MAX), None);
+    }
+
+    #[actix_web::test]
+    async fn overflowing_n_is_bad_request() {
+        let app = actix_test::init_service(
+            App::new().route("/api/compute", web::post().to(compute_fibonacci)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/compute")
+            .set_json(serde_json::json!({ "n": 1000 }))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body: serde_json::Value = actix_test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "validation_error");
+        assert_eq!(body["error"]["details"][0]["field"], "n");
+    }
+
+    #[test]
+    fn paginate_defaults_to_first_page() {
+        let page = paginate((0..50).collect(), None, None);
+        assert_eq!(page.items, (0..DEFAULT_PAGE_LIMIT).collect::<Vec<_>>());
+        assert_eq!(
+            (page.total, page.limit, page.offset),
+            (50, DEFAULT_PAGE_LIMIT, 0)
+        );
+    }
+
+    #[test]
+    fn paginate_returns_requested_window() {
+        let page = paginate((0..50).collect(), Some(5), Some(10));
+        assert_eq!(page.items, vec![10, 11, 12, 13, 14]);
+        assert_eq!((page.total, page.limit, page.offset), (50, 5, 10));
+
+        let page = paginate((0..50).collect::<Vec<_>>(), Some(0), None);
+        assert_eq!(page.limit, 1);
+        let page = paginate((0..50).collect::<Vec<_>>(), Some(1000), None);
+        assert_eq!(page.limit, MAX_PAGE_LIMIT);
+    }
+
+    #[test]
+    fn paginate_past_the_end_is_empty() {
+        let page = paginate((0..3).collect::<Vec<i32>>(), Some(10), Some(5));
+        assert!(page.items.is_empty());
+        assert_eq!(page.total, 3);
+    }
+
+    #[actix_web::test]
+    async fn users_total_tracks_creates_and_deletes() {
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+
+        let first = create(&app, "Gina", "gina@example.com").await;
+        create(&app, "Hank", "hank@example.com").await;
+        assert_eq!(metric_value("users_total", &[]), 2.0);
+
+        let req = actix_test::TestRequest::delete()
+            .uri(&format!("/api/users/{}", first.id))
+            .to_request();
+        actix_test::call_service(&app, req).await;
+        assert_eq!(metric_value("users_total", &[]), 1.0);
+    }
+
+    #[actix_web::test]
+    async fn log_level_update_requires_admin_token() {
+        use tracing_subscriber::{EnvFilter, layer::SubscriberExt};
+
+        let filter = observability::reloadable_filter(EnvFilter::new("info"));
+        let _subscriber =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(filter));
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(crate::auth::AdminToken(
+                    "s3cret".to_string(),
+                )))
+                .route("/admin/log-level", web::put().to(update_log_level)),
+        )
+        .await;
+        let put = |authorization: Option<&str>| {
+            let mut req = actix_test::TestRequest::put()
+                .uri("/admin/log-level")
+                .set_json(serde_json::json!({ "filter": "debug" }));
+            if let Some(value) = authorization {
+                req = req.insert_header(("authorization", value));
+            }
+            req.to_request()
+        };
+
+        for authorization in [None, Some("Bearer wrong"), Some("s3cret")] {
+            let res = actix_test::call_service(&app, put(authorization)).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{authorization:?}");
+            assert!(!tracing::enabled!(tracing::Level::DEBUG));
+        }
+
+        // Unauthenticated bodies are never parsed, so malformed ones are refused the same way
+        let req = actix_test::TestRequest::put()
+            .uri("/admin/log-level")
+            .insert_header(("content-type", "application/json"))
+            .set_payload("{not json")
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = actix_test::call_service(&app, put(Some("Bearer s3cret"))).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(tracing::enabled!(tracing::Level::DEBUG));
+    }
+
+    #[test]
+    fn readiness_waits_for_telemetry_and_store() {
+        let ready = ReadinessChecks {
+            telemetry: true,
+            user_store: true,
+            collector: true,
+            collector_required: false,
+        };
+        assert!(ready.is_ready());
+        assert_eq!(ready.status(), "ready");
+
+        for not_ready in [
+            ReadinessChecks {
+                telemetry: false,
+                ..ready
+            },
+            ReadinessChecks {
+                user_store: false,
+                ..ready
+            },
+        ] {
+            assert!(!not_ready.is_ready(), "{not_ready:?}");
+            assert_eq!(not_ready.status(), "not_ready");
+        }
     }
 
-    debug!("Computing fib({})", n);
-    compute_fib_recursive(n - 1) + compute_fib_recursive(n - 2)
+    #[actix_web::test]
+    async fn readiness_answers_503_until_ready() {
+        // Telemetry is never set up in tests, and no store is registered here
+        let app =
+            actix_test::init_service(App::new().route("/readyz", web::get().to(readiness))).await;
+
+        let req = actix_test::TestRequest::get().uri("/readyz").to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: serde_json::Value = actix_test::read_body_json(res).await;
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["checks"]["user_store"], false);
+    }
+
+    #[actix_web::test]
+    async fn async_fibonacci_matches_and_stays_in_the_request_span() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(App::new().route(
+            "/api/compute/async",
+            web::post().to(compute_fibonacci_async),
+        ))
+        .await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/compute/async")
+            .set_json(serde_json::json!({ "n": 50 }))
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["result"], fib_iterative(50).unwrap());
+
+        let spans = tracing.spans_named("compute_fibonacci_async");
+        assert_eq!(spans.len(), 1);
+        assert!(
+            spans[0]
+                .events
+                .iter()
+                .any(|event| event.name == "Computing fibonacci on blocking thread"),
+            "blocking-thread event recorded on the handler span"
+        );
+    }
+
+    #[actix_web::test]
+    async fn db_query_span_is_nested_under_list_users() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/api/users")
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let list = tracing.spans_named("list_users");
+        let query = tracing.spans_named("db.query");
+        assert_eq!((list.len(), query.len()), (1, 1));
+        assert_eq!(query[0].parent_span_id, list[0].span_context.span_id());
+        assert_eq!(
+            query[0].span_context.trace_id(),
+            list[0].span_context.trace_id()
+        );
+        assert_eq!(attribute(&query[0], "db.system").as_deref(), Some("memory"));
+        assert_eq!(
+            attribute(&query[0], "db.operation").as_deref(),
+            Some("select")
+        );
+    }
+
+    #[actix_web::test]
+    async fn invalid_create_records_validation_events() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/users")
+            .set_json(serde_json::json!({ "name": "Ivy", "email": "not-an-email" }))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let span = &tracing.spans_named("create_user")[0];
+        let event = span
+            .events
+            .iter()
+            .find(|event| event.name == "Invalid field provided")
+            .expect("validation failure event");
+        assert_eq!(
+            event_attribute(event, "validation.field").as_deref(),
+            Some("email")
+        );
+        assert_eq!(
+            event_attribute(event, "validation.result").as_deref(),
+            Some("invalid")
+        );
+        assert!(event_attribute(event, "reason").is_some());
+    }
+
+    #[actix_web::test]
+    async fn valid_create_records_each_checked_field() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+
+        create(&app, "Ivy", "ivy@example.com").await;
+
+        let span = &tracing.spans_named("create_user")[0];
+        let passed: Vec<_> = span
+            .events
+            .iter()
+            .filter(|event| event.name == "Validation passed")
+            .collect();
+        let fields: Vec<_> = passed
+            .iter()
+            .map(|event| event_attribute(event, "validation.field"))
+            .collect();
+        assert_eq!(
+            fields,
+            [Some("name".to_string()), Some("email".to_string())]
+        );
+        for event in passed {
+            assert_eq!(
+                event_attribute(event, "validation.result").as_deref(),
+                Some("valid")
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn search_filters_by_name_and_domain() {
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+        create(&app, "Alice Smith", "alice@example.com").await;
+        create(&app, "Bob", "bob@corp.io").await;
+        create(&app, "Alicia", "alicia@corp.io").await;
+
+        let search = |query: &str| {
+            actix_test::TestRequest::get()
+                .uri(&format!("/api/users/search{query}"))
+                .to_request()
+        };
+        let names = |page: serde_json::Value| -> Vec<String> {
+            page["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|user| user["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let page = actix_test::call_and_read_body_json(&app, search("?name_contains=ALI")).await;
+        assert_eq!(names(page), ["Alice Smith", "Alicia"]);
+
+        let page = actix_test::call_and_read_body_json(&app, search("?email_domain=Corp.io")).await;
+        assert_eq!(names(page), ["Alicia", "Bob"]);
+
+        let page = actix_test::call_and_read_body_json(
+            &app,
+            search("?name_contains=ali&email_domain=corp.io"),
+        )
+        .await;
+        assert_eq!(names(page), ["Alicia"]);
+
+        // A domain only matches after the '@', not as a suffix of it
+        let page = actix_test::call_and_read_body_json(&app, search("?email_domain=io")).await;
+        assert!(names(page).is_empty());
+
+        let page: serde_json::Value = actix_test::call_and_read_body_json(&app, search("")).await;
+        assert_eq!(page["total"], 3);
+    }
+
+    #[actix_web::test]
+    async fn computation_is_observed_once() {
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(
+            App::new().route("/api/compute", web::post().to(compute_fibonacci)),
+        )
+        .await;
+        let observations = || metric_value("fibonacci_computation_seconds", &[("n_range", "0-9")]);
+        let before = observations();
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/compute")
+            .set_json(serde_json::json!({ "n": 7 }))
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["result"], 13);
+
+        assert_eq!(observations(), before + 1.0);
+    }
+
+    #[actix_web::test]
+    async fn trace_context_reports_inbound_baggage() {
+        let _tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(crate::custom_middleware::RequestIdMiddleware::new(
+                    actix_web::http::header::HeaderName::from_static("x-request-id"),
+                    Duration::from_secs(60),
+                ))
+                .route("/api/trace-context", web::get().to(trace_context)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/api/trace-context")
+            .insert_header((
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ))
+            .insert_header(("baggage", "tenant.id=acme,user.tier=gold"))
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(body["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(
+            body["baggage"],
+            serde_json::json!({ "tenant.id": "acme", "user.tier": "gold" })
+        );
+    }
+
+    #[actix_web::test]
+    async fn serialize_span_records_body_size() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/api/users")
+            .to_request();
+        let body = actix_test::call_and_read_body(&app, req).await;
+
+        let spans = tracing.spans_named("serialize");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(attribute(&spans[0], "format").as_deref(), Some("json"));
+        let bytes: usize = attribute(&spans[0], "bytes").unwrap().parse().unwrap();
+        assert!(bytes > 0);
+        assert_eq!(bytes, body.len());
+    }
+
+    #[test]
+    fn recursive_fibonacci_spans_stay_bounded() {
+        let tracing = TestTracing::install();
+
+        assert_eq!(compute_fib_recursive(25), 75025);
+
+        assert_eq!(tracing.spans_named("compute_fib_recursive").len(), 1);
+        // Only the entry point's two children, not one span per call (242785 for n=25)
+        assert_eq!(tracing.spans_named("fib_recursive").len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn avatar_is_svg_with_recorded_size() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+        let created = create(&app, "Carol", "carol@example.com").await;
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/api/users/{}/avatar", created.id))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-type").unwrap(), "image/svg+xml");
+        let body = actix_test::read_body(res).await;
+
+        assert!(!body.is_empty());
+        let spans = tracing.spans_named("render_avatar");
+        assert_eq!(spans.len(), 1);
+        let bytes: usize = attribute(&spans[0], "bytes").unwrap().parse().unwrap();
+        assert_eq!(bytes, body.len());
+
+        let req = actix_test::TestRequest::get()
+            .uri("/api/users/404/avatar")
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn trace_context_reports_the_handler_span() {
+        let app = actix_test::init_service(
+            App::new().route("/api/trace-context", web::get().to(trace_context)),
+        )
+        .await;
+
+        // No OTEL layer installed: nulls rather than an error
+        let req = actix_test::TestRequest::get()
+            .uri("/api/trace-context")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert!(body["trace_id"].is_null());
+        assert!(body["span_id"].is_null());
+
+        let tracing = TestTracing::install();
+        let req = actix_test::TestRequest::get()
+            .uri("/api/trace-context")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+
+        let trace_id = body["trace_id"].as_str().unwrap();
+        assert!(!trace_id.is_empty());
+        let spans = tracing.spans_named("trace_context");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(trace_id, spans[0].span_context.trace_id().to_string());
+        assert_eq!(body["span_id"], spans[0].span_context.span_id().to_string());
+    }
+
+    #[actix_web::test]
+    async fn idempotency_key_creates_once() {
+        let _metrics = lock_metrics().await;
+        let store = web::Data::new(UserStore::default());
+        let app = actix_test::init_service(users_app(store.clone())).await;
+        let post = |key: &str, name: &str| {
+            actix_test::TestRequest::post()
+                .uri("/api/users")
+                .insert_header((IDEMPOTENCY_KEY_HEADER, key.to_string()))
+                .set_json(serde_json::json!({ "name": name, "email": "carol@example.com" }))
+                .to_request()
+        };
+
+        let first: User = actix_test::call_and_read_body_json(&app, post("key-1", "Carol")).await;
+        assert_eq!(store.len(), 1);
+
+        // The retry gets the original user back, even with a different body
+        let res = actix_test::call_service(&app, post("key-1", "Dave")).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let retried: User = actix_test::read_body_json(res).await;
+        assert_eq!(retried.id, first.id);
+        assert_eq!(retried.name, "Carol");
+        assert_eq!(store.len(), 1);
+
+        let fresh: User = actix_test::call_and_read_body_json(&app, post("key-2", "Dave")).await;
+        assert_ne!(fresh.id, first.id);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn batch_reports_each_entry() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let store = web::Data::new(UserStore::default());
+        let app = actix_test::init_service(users_app(store.clone())).await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/users/batch")
+            .set_json(serde_json::json!([
+                { "name": "Carol", "email": "carol@example.com" },
+                { "name": "", "email": "nobody@example.com" },
+                { "name": "Dave", "email": "dave@" },
+                { "name": "Erin", "email": "erin@example.com" },
+            ]))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+        let body: serde_json::Value = actix_test::read_body_json(res).await;
+
+        assert_eq!(body["total"], 4);
+        assert_eq!(body["succeeded"], 2);
+        assert_eq!(body["failed"], 2);
+        let statuses: Vec<_> = body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["status"].as_u64().unwrap())
+            .collect();
+        assert_eq!(statuses, [201, 400, 400, 201]);
+        assert_eq!(body["results"][1]["error"]["code"], "validation_error");
+        assert!(body["results"][1].get("id").is_none());
+        assert_eq!(store.len(), 2);
+
+        let spans = tracing.spans_named("create_users_batch");
+        assert_eq!(attribute(&spans[0], "succeeded").as_deref(), Some("2"));
+        assert_eq!(attribute(&spans[0], "failed").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn collector_probe_degrades_or_fails_readiness() {
+        let ready = ReadinessChecks {
+            telemetry: true,
+            user_store: true,
+            collector: true,
+            collector_required: false,
+        };
+
+        for collector_required in [false, true] {
+            let reachable = ReadinessChecks {
+                collector_required,
+                ..ready
+            };
+            assert!(reachable.is_ready());
+            assert_eq!(reachable.status(), "ready");
+        }
+
+        let unreachable = ReadinessChecks {
+            collector: false,
+            ..ready
+        };
+        assert!(
+            unreachable.is_ready(),
+            "an optional collector only degrades"
+        );
+        assert_eq!(unreachable.status(), "degraded");
+
+        let required = ReadinessChecks {
+            collector_required: true,
+            ..unreachable
+        };
+        assert!(!required.is_ready());
+        assert_eq!(required.status(), "not_ready");
+    }
+
+    #[actix_web::test]
+    async fn passed_deadline_is_gateway_timeout_without_work() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(crate::custom_middleware::RequestIdMiddleware::new(
+                    actix_web::http::header::HeaderName::from_static("x-request-id"),
+                    Duration::from_secs(60),
+                ))
+                .route("/api/compute", web::post().to(compute_fibonacci)),
+        )
+        .await;
+        let observations = || metric_value("fibonacci_computation_seconds", &[("n_range", "0-9")]);
+        let before = observations();
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/compute")
+            // One second after the epoch: long gone
+            .insert_header((crate::deadline::REQUEST_DEADLINE_HEADER, "1000"))
+            .set_json(serde_json::json!({ "n": 7 }))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body: serde_json::Value = actix_test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "deadline_exceeded");
+        assert_eq!(observations(), before, "nothing was computed");
+
+        let span = tracing.request_span("/api/compute").unwrap();
+        assert_eq!(
+            attribute(&span, "deadline.remaining_ms").as_deref(),
+            Some("0")
+        );
+        assert_eq!(
+            attribute(&span, "deadline.exceeded").as_deref(),
+            Some("true")
+        );
+    }
+
+    #[actix_web::test]
+    async fn create_reports_every_invalid_field() {
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+        let post = |body: serde_json::Value| {
+            actix_test::TestRequest::post()
+                .uri("/api/users")
+                .set_json(body)
+                .to_request()
+        };
+        let fields = |body: &serde_json::Value| -> Vec<String> {
+            body["error"]["details"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|detail| detail["field"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let res = actix_test::call_service(
+            &app,
+            post(serde_json::json!({ "name": "  ", "email": "nobody" })),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = actix_test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "validation_error");
+        assert_eq!(fields(&body), ["name", "email"]);
+
+        let long_name = "x".repeat(MAX_NAME_LEN + 1);
+        let body: serde_json::Value = actix_test::call_and_read_body_json(
+            &app,
+            post(serde_json::json!({ "name": long_name, "email": "a@b" })),
+        )
+        .await;
+        assert_eq!(fields(&body), ["name", "email"]);
+        assert!(
+            body["error"]["details"][0]["message"]
+                .as_str()
+                .unwrap()
+                .contains("at most")
+        );
+    }
+
+    #[actix_web::test]
+    async fn linked_subtasks_link_back_to_the_request() {
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            App::new().route("/api/linked-tasks", web::get().to(linked_tasks)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/api/linked-tasks")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+
+        let parent = &tracing.spans_named("linked_tasks")[0];
+        assert_eq!(body["trace_id"], parent.span_context.trace_id().to_string());
+        assert_eq!(attribute(parent, "links.count").as_deref(), Some("2"));
+
+        let subtasks = tracing.spans_named("linked_task");
+        assert_eq!(subtasks.len(), LINKED_TASK_COUNT as usize);
+        for subtask in &subtasks {
+            assert_ne!(
+                subtask.span_context.trace_id(),
+                parent.span_context.trace_id(),
+                "subtasks run in traces of their own"
+            );
+            assert_eq!(subtask.links.len(), 1);
+            assert!(
+                subtask
+                    .links
+                    .iter()
+                    .all(|link| link.span_context == parent.span_context)
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn export_streams_one_record_per_line() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+        for (name, email) in [
+            ("Carol", "carol@example.com"),
+            ("Dave", "dave@example.com"),
+            ("Erin", "erin@example.com"),
+        ] {
+            create(&app, name, email).await;
+        }
+
+        let req = actix_test::TestRequest::get()
+            .uri("/api/users/export")
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            "application/x-ndjson"
+        );
+        let body = actix_test::read_body(res).await;
+
+        let users: Vec<User> = body
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(users.len(), 3);
+
+        let span = &tracing.spans_named("export_users")[0];
+        assert_eq!(attribute(span, "records").as_deref(), Some("3"));
+    }
+
+    #[actix_web::test]
+    async fn msgpack_response_decodes_into_user() {
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(users_app(web::Data::new(UserStore::default()))).await;
+        let created = create(&app, "Carol", "carol@example.com").await;
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/api/users/{}", created.id))
+            .insert_header(("accept", "application/msgpack"))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            "application/msgpack"
+        );
+        let body = actix_test::read_body(res).await;
+
+        let fetched: User = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(fetched.id, created.id);
+        assert_eq!(fetched.name, "Carol");
+        assert_eq!(fetched.email, "carol@example.com");
+    }
+
+    #[actix_web::test]
+    async fn debug_error_kinds_map_to_status_and_metric() {
+        let _metrics = lock_metrics().await;
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(crate::custom_middleware::RequestIdMiddleware::new(
+                    actix_web::http::header::HeaderName::from_static("x-request-id"),
+                    Duration::from_secs(60),
+                ))
+                .route("/api/debug/error", web::get().to(debug_error)),
+        )
+        .await;
+        let kinds = ["validation", "not_found", "internal", "timeout"];
+        let errors = |kind| metric_value("app_errors_total", &[("kind", kind)]);
+
+        for (kind, status) in [
+            ("validation", StatusCode::BAD_REQUEST),
+            ("not_found", StatusCode::NOT_FOUND),
+            ("internal", StatusCode::INTERNAL_SERVER_ERROR),
+            ("timeout", StatusCode::SERVICE_UNAVAILABLE),
+        ] {
+            let before = kinds.map(errors);
+
+            let req = actix_test::TestRequest::get()
+                .uri(&format!("/api/debug/error?kind={kind}"))
+                .to_request();
+            let res = actix_test::call_service(&app, req).await;
+            assert_eq!(res.status(), status, "{kind}");
+
+            for (label, before) in kinds.into_iter().zip(before) {
+                let expected = if label == kind { before + 1.0 } else { before };
+                assert_eq!(errors(label), expected, "{kind} -> {label}");
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn failing_check_degrades_health() {
+        // Telemetry is never set up in tests, so that check always fails here
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(UserStore::default()))
+                .route("/api/health", web::get().to(health_check)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/api/health")
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = actix_test::read_body_json(res).await;
+
+        assert_eq!(body["status"], "degraded");
+        assert_eq!(
+            body["checks"],
+            serde_json::json!({
+                "telemetry": "failing",
+                "user_store": "ok",
+                "external_service": "ok",
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn only_failed_creates_record_the_body() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(
+            users_app(web::Data::new(UserStore::default())).wrap(
+                crate::custom_middleware::RequestIdMiddleware::new(
+                    actix_web::http::header::HeaderName::from_static("x-request-id"),
+                    Duration::from_secs(60),
+                )
+                .with_error_body_capture(true),
+            ),
+        )
+        .await;
+        let post = |request_id: &str, name: String| {
+            actix_test::TestRequest::post()
+                .uri("/api/users")
+                .insert_header(("x-request-id", request_id.to_string()))
+                .set_json(serde_json::json!({ "name": name, "email": "carol@example.com" }))
+                .to_request()
+        };
+        let request_span = |request_id: &str| {
+            tracing
+                .spans()
+                .into_iter()
+                .find(|span| {
+                    attribute(span, "path").as_deref() == Some("/api/users")
+                        && attribute(span, "request_id").as_deref() == Some(request_id)
+                })
+                .unwrap()
+        };
+
+        let res = actix_test::call_service(&app, post("capture-ok", "Carol".to_string())).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        actix_test::read_body(res).await;
+        let created = request_span("capture-ok");
+        assert!(attribute(&created, "http.request.body").is_none());
+
+        // Far over both the name limit and the captured prefix
+        let res = actix_test::call_service(&app, post("capture-bad", "x".repeat(4096))).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        actix_test::read_body(res).await;
+        let rejected = request_span("capture-bad");
+        let body = attribute(&rejected, "http.request.body").unwrap();
+        assert!(body.contains(r#""name":"xxx"#));
+        assert_eq!(body.len(), 1024);
+        assert_eq!(
+            attribute(&rejected, "http.request.body.truncated").as_deref(),
+            Some("true")
+        );
+    }
+
+    #[actix_web::test]
+    async fn compute_batch_reports_out_of_range_items() {
+        let _metrics = lock_metrics().await;
+        let tracing = TestTracing::install();
+        let app = actix_test::init_service(App::new().route(
+            "/api/compute/batch",
+            web::post().to(compute_fibonacci_batch),
+        ))
+        .await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/compute/batch")
+            .set_json(serde_json::json!([{ "n": 10 }, { "n": 500 }, { "n": 20 }]))
+            .to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+        let body: serde_json::Value = actix_test::read_body_json(res).await;
+
+        assert_eq!(body["succeeded"], 2);
+        assert_eq!(body["failed"], 1);
+        let results = body["results"].as_array().unwrap();
+        assert_eq!(results[0]["result"], 55);
+        assert_eq!(results[1]["status"], 400);
+        assert!(results[1].get("result").is_none());
+        assert_eq!(results[1]["error"]["code"], "validation_error");
+        assert_eq!(results[2]["result"], 6765);
+
+        let items = tracing.spans_named("fibonacci");
+        assert_eq!(items.len(), 3);
+        let overflowed: Vec<_> = items
+            .iter()
+            .filter(|span| attribute(span, "overflow").as_deref() == Some("true"))
+            .map(|span| attribute(span, "n").unwrap())
+            .collect();
+        assert_eq!(overflowed, ["500"]);
+    }
+
+    #[test]
+    fn batch_items_past_the_deadline_are_gateway_timeouts() {
+        let _metrics = lock_metrics_blocking();
+        // One second after the epoch: long gone
+        let deadline = Deadline::from_header(Some(
+            &actix_web::http::header::HeaderValue::from_static("1000"),
+        ));
+        let items = [FibonacciRequest { n: 10 }, FibonacciRequest { n: 25 }];
+
+        let (results, compute_time) =
+            compute_batch_items(&items, deadline, &RequestId("req-1".to_string()));
+
+        assert_eq!(compute_time, Duration::ZERO, "nothing was computed");
+        for result in results {
+            assert_eq!(result.status, StatusCode::GATEWAY_TIMEOUT.as_u16());
+            assert_eq!(result.result, None);
+            assert_eq!(result.error.unwrap().code, "deadline_exceeded");
+        }
+    }
 }
@@ -0,0 +1,56 @@
+/// Idempotency keys for retried POSTs
+///
+/// Clients send an `Idempotency-Key` header; a retry carrying a key seen within the TTL gets
+/// the original result back instead of repeating the side effect. Like `UserStore`, the
+/// cache lives in memory and is shared across workers via `web::Data`.
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Request header carrying the client-chosen key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Results of recent keyed requests, forgotten after `ttl`
+pub struct IdempotencyCache<T> {
+    entries: Mutex<HashMap<String, (Instant, T)>>,
+    ttl: Duration,
+}
+
+impl<T: Clone> IdempotencyCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    /// Return the cached value for `key`, or run `create` and cache its success
+    ///
+    /// The lock is held while `create` runs so concurrent retries with one key cannot both
+    /// create. Errors are not cached, letting the client fix the request and retry. The flag
+    /// is `true` when the value came from the cache.
+    pub fn get_or_try_insert_with<E>(
+        &self,
+        key: &str,
+        create: impl FnOnce() -> Result<T, E>,
+    ) -> Result<(T, bool), E> {
+        let mut entries = self.lock();
+        let now = Instant::now();
+        entries.retain(|_, (stored_at, _)| now.duration_since(*stored_at) < self.ttl);
+
+        if let Some((_, value)) = entries.get(key) {
+            return Ok((value.clone(), true));
+        }
+
+        let value = create()?;
+        entries.insert(key.to_string(), (now, value.clone()));
+        Ok((value, false))
+    }
+
+    // A panic while holding the lock cannot leave the map half-updated, so recover from poisoning
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (Instant, T)>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,281 @@
+/// Request and trace IDs on every log line
+///
+/// The JSON formatter nests span fields under `span`/`spans`, so a log emitted from a nested
+/// span only carries `request_id` somewhere in that list, and Loki queries such as
+/// `{job="rust-app"} | json | request_id="..."` miss it. Trace context isn't a span field at
+/// all, so without help a log line can't be linked to its trace. This module shows how to:
+/// - Propagate a span field down to descendant spans with a custom `Layer`
+/// - Read the OpenTelemetry trace and span IDs that `tracing-opentelemetry` keeps per span
+/// - Decorate every formatted event with those fields via a `FormatEvent` wrapper
+use std::fmt::{self, Write as _};
+
+use opentelemetry::trace::{SpanId, TraceContextExt, TraceId};
+
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+    span,
+};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::{
+    Layer,
+    fmt::{FmtContext, FormatEvent, FormatFields, format::Writer},
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+};
+
+/// Span field holding the correlation ID set by `RequestIdMiddleware`
+const REQUEST_ID_FIELD: &str = "request_id";
+
+/// Request ID stored in span extensions, inherited by every descendant span
+#[derive(Clone, Debug)]
+struct RequestIdExtension(String);
+
+/// Captures the `request_id` field from span attributes
+#[derive(Default)]
+struct RequestIdVisitor(Option<String>);
+
+impl Visit for RequestIdVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == REQUEST_ID_FIELD {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == REQUEST_ID_FIELD {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+/// Layer that remembers the request ID of each span, taken from its own `request_id` field or
+/// inherited from its parent
+pub struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut visitor = RequestIdVisitor::default();
+        attrs.record(&mut visitor);
+
+        let request_id = visitor.0.map(RequestIdExtension).or_else(|| {
+            span.parent()
+                .and_then(|parent| parent.extensions().get::<RequestIdExtension>().cloned())
+        });
+
+        if let Some(request_id) = request_id {
+            span.extensions_mut().insert(request_id);
+        }
+    }
+}
+
+/// Trace and span IDs of the OpenTelemetry span backing `span`, if it is being traced
+///
+/// Root spans carry their own trace ID; child spans and spans continuing a remote trace
+/// take it from their parent context.
+fn otel_ids<S>(span: &SpanRef<'_, S>) -> Option<(TraceId, SpanId)>
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    let extensions = span.extensions();
+    let data = extensions.get::<OtelData>()?;
+
+    let trace_id = data.builder.trace_id.or_else(|| {
+        let parent = data.parent_cx.span();
+        let parent = parent.span_context();
+        parent.is_valid().then(|| parent.trace_id())
+    })?;
+
+    Some((trace_id, data.builder.span_id?))
+}
+
+/// Event formatter that adds top-level `request_id`, `trace_id` and `span_id` to JSON log lines
+///
+/// Wraps another JSON formatter; fields that don't apply (events outside any request span,
+/// untraced spans, or trace context disabled) are left out.
+pub struct WithLogContext<F> {
+    inner: F,
+    trace_context: bool,
+}
+
+impl<F> WithLogContext<F> {
+    /// `trace_context` controls the `trace_id`/`span_id` fields (`LOG_TRACE_CONTEXT`)
+    pub fn new(inner: F, trace_context: bool) -> Self {
+        Self {
+            inner,
+            trace_context,
+        }
+    }
+}
+
+impl<S, N, F> FormatEvent<S, N> for WithLogContext<F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let Some(span) = ctx.event_scope().and_then(|mut scope| scope.next()) else {
+            return self.inner.format_event(ctx, writer, event);
+        };
+
+        let request_id = span.extensions().get::<RequestIdExtension>().cloned();
+        let otel_ids = self.trace_context.then(|| otel_ids(&span)).flatten();
+
+        if request_id.is_none() && otel_ids.is_none() {
+            return self.inner.format_event(ctx, writer, event);
+        }
+
+        let mut fields = String::new();
+        if let Some(RequestIdExtension(request_id)) = request_id {
+            let request_id = serde_json::to_string(&request_id).map_err(|_| fmt::Error)?;
+            write!(fields, "\"{REQUEST_ID_FIELD}\":{request_id},")?;
+        }
+        if let Some((trace_id, span_id)) = otel_ids {
+            write!(
+                fields,
+                "\"trace_id\":\"{trace_id}\",\"span_id\":\"{span_id}\","
+            )?;
+        }
+
+        let mut line = String::new();
+        self.inner
+            .format_event(ctx, Writer::new(&mut line), event)?;
+
+        // Splice the fields in as the first keys of the JSON object
+        match line.strip_prefix('{') {
+            Some(rest) => write!(writer, "{{{fields}{rest}"),
+            None => writer.write_str(&line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::InMemorySpanExporter;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Captures formatted log lines in memory
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Captured {
+        fn lines(&self) -> Vec<serde_json::Value> {
+            let bytes = self.0.lock().unwrap();
+            std::str::from_utf8(&bytes)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// JSON subscriber as `init_tracing` builds it, without the OpenTelemetry layer
+    fn json_subscriber(captured: &Captured) -> impl Subscriber + Send + Sync {
+        let captured = captured.clone();
+        tracing_subscriber::registry().with(RequestIdLayer).with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(move || captured.clone())
+                .json()
+                .map_event_format(|format| WithLogContext::new(format, true)),
+        )
+    }
+
+    #[test]
+    fn nested_log_carries_request_id() {
+        let captured = Captured::default();
+
+        tracing::subscriber::with_default(json_subscriber(&captured), || {
+            let request = tracing::info_span!("http_request", request_id = "req-42");
+            let _request = request.enter();
+            let query = tracing::info_span!("db.query");
+            let _query = query.enter();
+            tracing::info!("nested");
+        });
+        tracing::subscriber::with_default(json_subscriber(&captured), || {
+            tracing::info!("outside");
+        });
+
+        let lines = captured.lines();
+        assert_eq!(lines[0]["request_id"], "req-42");
+        assert_eq!(lines[0]["fields"]["message"], "nested");
+        assert!(lines[1].get("request_id").is_none());
+    }
+
+    #[test]
+    fn traced_log_carries_trace_and_span_ids() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let traced_json_subscriber = |captured: &Captured, trace_context| {
+            let captured = captured.clone();
+            tracing_subscriber::registry()
+                .with(RequestIdLayer)
+                .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")))
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_writer(move || captured.clone())
+                        .json()
+                        .map_event_format(move |format| WithLogContext::new(format, trace_context)),
+                )
+        };
+        let request = || {
+            let request = tracing::info_span!("http_request", request_id = "req-42");
+            let _request = request.enter();
+            let query = tracing::info_span!("db.query");
+            let _query = query.enter();
+            tracing::info!("nested");
+        };
+
+        let captured = Captured::default();
+        tracing::subscriber::with_default(traced_json_subscriber(&captured, true), request);
+        let disabled = Captured::default();
+        tracing::subscriber::with_default(traced_json_subscriber(&disabled, false), request);
+
+        provider.force_flush();
+        let query = exporter
+            .spans()
+            .into_iter()
+            .find(|span| span.name == "db.query")
+            .unwrap();
+        let line = &captured.lines()[0];
+        assert_eq!(line["request_id"], "req-42");
+        assert_eq!(line["trace_id"], query.span_context.trace_id().to_string());
+        assert_eq!(line["span_id"], query.span_context.span_id().to_string());
+
+        let line = &disabled.lines()[0];
+        assert_eq!(line["request_id"], "req-42");
+        assert!(line.get("trace_id").is_none());
+        assert!(line.get("span_id").is_none());
+    }
+}
@@ -1,14 +1,30 @@
+use std::sync::Arc;
+
 use actix_web::{App, HttpServer, middleware, web};
-use opentelemetry::global;
-use tracing::info;
+use tracing::{error, info};
 
+mod auth;
+mod config;
 mod custom_middleware;
+mod deadline;
+mod error;
 mod handlers;
+mod idempotency;
+mod log_context;
 mod metrics;
 mod observability;
+mod propagation;
+mod response_format;
+mod store;
+#[cfg(test)]
+mod test_support;
+mod validation;
 
-use metrics::metrics_handler;
-use observability::setup_telemetry;
+use config::ServerConfig;
+use idempotency::IdempotencyCache;
+use metrics::{metrics_handler, reset_metrics_handler};
+use observability::{setup_telemetry, shutdown_telemetry};
+use store::UserStore;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -17,29 +33,220 @@ async fn main() -> std::io::Result<()> {
 
     info!("Starting OpenTelemetry Tutorial Application");
 
+    let server_config = ServerConfig::from_env().map_err(|err| {
+        error!("Invalid server configuration: {err}");
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
+    })?;
+    let bind_address = server_config.bind_address();
+    let request_timeout = server_config.request_timeout;
+    let slow_request_threshold = server_config.slow_request_threshold;
+    let max_body_bytes = server_config.max_body_bytes;
+    let request_id_header = server_config.request_id_header.clone();
+    let cors_allowed_origins = server_config.cors_allowed_origins.clone();
+    let debug_endpoints = server_config.debug_endpoints;
+
+    let admin_token = server_config
+        .admin_token
+        .clone()
+        .map(|token| web::Data::new(auth::AdminToken(token)));
+    if admin_token.is_none() {
+        info!("ADMIN_TOKEN not set, /admin endpoints are disabled");
+    }
+
+    // Fixed attributes from SPAN_GLOBAL_ATTRS, shared by every worker's request spans
+    let span_global_attributes: Arc<[(String, String)]> =
+        custom_middleware::global_span_attributes().into();
+    // Off unless CAPTURE_ERROR_BODIES is set; bodies can carry personal data
+    let capture_error_bodies = custom_middleware::capture_error_bodies();
+
+    // Token buckets are shared across workers; disabled unless RATE_LIMIT_RPS is set
+    let rate_limiter = server_config.rate_limit_rps.map(|rps| {
+        Arc::new(custom_middleware::RateLimiter::new(
+            rps,
+            server_config.rate_limit_burst,
+        ))
+    });
+
+    // Shared in-memory user store
+    let user_store = web::Data::new(UserStore::with_seed_users());
+    metrics::set_users_total(user_store.len());
+    metrics::set_build_info();
+
+    // Users created per Idempotency-Key, so retried POSTs don't create duplicates
+    let created_users = web::Data::new(IdempotencyCache::<handlers::User>::new(
+        server_config.idempotency_ttl,
+    ));
+
     // Create HTTP server
-    let server = HttpServer::new(|| {
+    let server = HttpServer::new(move || {
         App::new()
-            .wrap(middleware::Logger::default())
-            .wrap(custom_middleware::RequestIdMiddleware)
+            .app_data(user_store.clone())
+            .app_data(created_users.clone())
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(max_body_bytes)
+                    .error_handler(error::json_error_handler),
+            )
+            // Compression negotiates via Accept-Encoding, so clients that don't ask (e.g.
+            // plain scrapers) still get identity responses
+            .wrap(custom_middleware::UncompressedSizeMiddleware)
+            .wrap(middleware::Compress::default())
+            .wrap(custom_middleware::TimeoutMiddleware::new(request_timeout))
+            .wrap(custom_middleware::RateLimitMiddleware::new(
+                rate_limiter.clone(),
+            ))
+            .wrap(custom_middleware::DrainMiddleware)
+            // Without configured origins requests pass through untouched, and browsers keep
+            // blocking cross-origin calls as before
+            .wrap(middleware::Condition::new(
+                !cors_allowed_origins.is_empty(),
+                custom_middleware::cors(&cors_allowed_origins, &request_id_header),
+            ))
+            .wrap(
+                custom_middleware::RequestIdMiddleware::new(
+                    request_id_header.clone(),
+                    slow_request_threshold,
+                )
+                .with_span_attributes(span_global_attributes.clone())
+                .with_error_body_capture(capture_error_bodies),
+            )
             .route("/metrics", web::get().to(metrics_handler))
+            .route("/healthz", web::get().to(handlers::liveness))
+            .route("/readyz", web::get().to(handlers::readiness))
+            .configure(|cfg| {
+                if debug_endpoints {
+                    cfg.route("/metrics/reset", web::post().to(reset_metrics_handler));
+                }
+            })
+            // Without a token there is no way to authenticate callers, so leave them unmounted
+            .configure(|cfg| {
+                if let Some(token) = &admin_token {
+                    cfg.service(
+                        web::scope("/admin")
+                            .app_data(token.clone())
+                            .route("/log-level", web::put().to(handlers::update_log_level)),
+                    );
+                }
+            })
             .service(
                 web::scope("/api")
                     .route("/health", web::get().to(handlers::health_check))
+                    .route("/trace-context", web::get().to(handlers::trace_context))
+                    .route("/linked-tasks", web::get().to(handlers::linked_tasks))
                     .route("/users", web::get().to(handlers::list_users))
                     .route("/users", web::post().to(handlers::create_user))
+                    .route("/users/search", web::get().to(handlers::search_users))
+                    .route("/users/export", web::get().to(handlers::export_users))
+                    .route("/users/batch", web::post().to(handlers::create_users_batch))
                     .route("/users/{id}", web::get().to(handlers::get_user))
-                    .route("/compute", web::post().to(handlers::compute_fibonacci)),
+                    .route(
+                        "/users/{id}/avatar",
+                        web::get().to(handlers::get_user_avatar),
+                    )
+                    .route("/users/{id}", web::put().to(handlers::update_user))
+                    .route("/users/{id}", web::delete().to(handlers::delete_user))
+                    .route("/compute", web::post().to(handlers::compute_fibonacci))
+                    .route(
+                        "/compute/async",
+                        web::post().to(handlers::compute_fibonacci_async),
+                    )
+                    .route(
+                        "/compute/batch",
+                        web::post().to(handlers::compute_fibonacci_batch),
+                    )
+                    // Unmounted routes answer 404, so production never exposes them by default
+                    .configure(|cfg| {
+                        if debug_endpoints {
+                            cfg.route("/debug/error", web::get().to(handlers::debug_error));
+                        }
+                    }),
             )
     })
-    .bind("127.0.0.1:8080")?
-    .run();
+    // Signals are handled below so shutdown can also flush telemetry
+    .disable_signals()
+    .workers(server_config.workers)
+    .backlog(server_config.backlog)
+    .shutdown_timeout(server_config.shutdown_timeout.as_secs())
+    .bind(&bind_address)?;
+
+    // Report the address actually bound (e.g. when APP_PORT=0 picks a free port)
+    for addr in server.addrs() {
+        info!("Server running on http://{addr}");
+    }
+    info!(
+        workers = server_config.workers,
+        backlog = server_config.backlog,
+        "Server tuning applied"
+    );
+
+    let server = server.run();
 
-    info!("Server running on http://127.0.0.1:8080");
+    // Without a scraper, periodic summaries in the logs are the only view of the counters
+    let metrics_summary = server_config.metrics_log_interval.map(|interval| {
+        info!(
+            interval_secs = interval.as_secs(),
+            "Logging metrics summaries periodically"
+        );
+        actix_web::rt::spawn(metrics::log_summaries(interval))
+    });
+
+    // On SIGTERM/SIGINT stop accepting connections and drain in-flight requests
+    let server_handle = server.handle();
+    let drain_delay = server_config.drain_delay;
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutdown signal received, draining in-flight requests");
+
+        // Fail readiness and answer 503 first, giving load balancers time to stop routing
+        // here before the listener closes
+        custom_middleware::start_draining();
+        tokio::time::sleep(drain_delay).await;
+
+        server_handle.stop(true).await;
+    });
 
     server.await?;
 
-    // Shutdown telemetry gracefully
-    global::shutdown_tracer_provider();
+    // Stop the summaries and log a last one, so the final partial interval isn't lost
+    if let Some(task) = metrics_summary {
+        task.abort();
+        metrics::log_summary();
+    }
+
+    // Shutdown telemetry gracefully, flushing pending spans and logs
+    shutdown_telemetry().await;
     Ok(())
 }
+
+/// Resolve when the process receives SIGINT (Ctrl+C) or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                error!("Failed to listen for SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
@@ -1,54 +1,475 @@
-use std::time::Duration;
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{
+        Mutex, OnceLock, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 
-use actix_web::{HttpResponse, Responder};
+use actix_web::{HttpRequest, HttpResponse, Responder, http::header::ACCEPT};
 use once_cell::sync::Lazy;
+use opentelemetry::{
+    KeyValue,
+    metrics::{Counter, Histogram, Meter, Unit},
+};
+use opentelemetry_sdk::metrics::{
+    Aggregation, InstrumentKind,
+    reader::{AggregationSelector, DefaultAggregationSelector},
+};
 use prometheus::{
-    Encoder, HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder, register_histogram_vec,
-    register_int_counter_vec, register_int_gauge_vec,
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, TextEncoder,
+    core::Collector,
+    exponential_buckets,
+    proto::{LabelPair, MetricFamily, MetricType},
+};
+
+use crate::{
+    auth::{has_bearer_token, unauthorized},
+    config::ConfigError,
 };
 
+/// Optional bearer token guarding `/metrics` (`METRICS_TOKEN`)
+static METRICS_TOKEN: Lazy<Option<String>> =
+    Lazy::new(|| crate::config::env_string("METRICS_TOKEN"));
+
+/// Collectors registered through `register`, keyed by metric name
+static REGISTERED: Lazy<Mutex<HashMap<String, Box<dyn Any + Send>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Register `collector` with the default registry, tolerating repeated registration
+///
+/// If a metric of the same name was already registered through here (e.g. an integration test
+/// building the app twice), the existing collector is returned instead of panicking on
+/// `AlreadyReg`, so both callers update the series that is actually scraped. A same-shaped
+/// collector registered elsewhere has no handle to reuse, so it is replaced by this one rather
+/// than leaving the caller with an instance that is never exported.
+fn register<C>(collector: C) -> C
+where
+    C: Collector + Clone + 'static,
+{
+    let name = collector
+        .desc()
+        .first()
+        .map(|desc| desc.fq_name.clone())
+        .unwrap_or_default();
+
+    let mut registered = REGISTERED.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(existing) = registered
+        .get(&name)
+        .and_then(|existing| existing.downcast_ref::<C>())
+    {
+        return existing.clone();
+    }
+
+    match prometheus::register(Box::new(collector.clone())) {
+        Ok(()) => {}
+        Err(prometheus::Error::AlreadyReg) => {
+            tracing::warn!(metric = %name, "Metric already registered elsewhere, replacing it");
+            prometheus::unregister(Box::new(collector.clone()))
+                .and_then(|()| prometheus::register(Box::new(collector.clone())))
+                .unwrap_or_else(|err| panic!("failed to re-register {name}: {err}"));
+        }
+        Err(err) => panic!("failed to register {name}: {err}"),
+    }
+
+    registered.insert(name, Box::new(collector.clone()));
+    collector
+}
+
 static HTTP_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
-    register_int_counter_vec!(
-        "http_requests_total",
-        "Total number of HTTP requests processed",
-        &["method", "endpoint", "status"]
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "http_requests_total",
+                "Total number of HTTP requests processed",
+            ),
+            &["method", "endpoint", "status"],
+        )
+        .expect("valid http_requests_total counter"),
+    )
+});
+
+static HTTP_REQUESTS_SLO_GOOD_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "http_requests_slo_good_total",
+                "HTTP requests meeting the SLO: non-5xx and faster than SLO_LATENCY_THRESHOLD_MS",
+            ),
+            &["method", "endpoint"],
+        )
+        .expect("valid http_requests_slo_good_total counter"),
     )
-    .expect("failed to register http_requests_total counter")
 });
 
 static HTTP_REQUEST_DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
-    register_histogram_vec!(
-        "http_request_duration_seconds",
-        "HTTP request latency in seconds",
-        &["method", "endpoint", "status"],
-        vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            )
+            .buckets(LATENCY_BUCKETS.clone()),
+            &["method", "endpoint", "status"],
+        )
+        .expect("valid http_request_duration_seconds histogram"),
     )
-    .expect("failed to register http_request_duration_seconds histogram")
 });
 
 static HTTP_REQUESTS_IN_FLIGHT: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!(
-        "http_requests_in_flight",
-        "In-flight HTTP requests",
-        &["method", "endpoint"]
+    register(
+        IntGaugeVec::new(
+            Opts::new("http_requests_in_flight", "In-flight HTTP requests"),
+            &["method", "endpoint"],
+        )
+        .expect("valid http_requests_in_flight gauge"),
+    )
+});
+
+static HTTP_REQUEST_SIZE_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new("http_request_size_bytes", "HTTP request body size in bytes")
+                .buckets(payload_size_buckets()),
+            &["method", "endpoint"],
+        )
+        .expect("valid http_request_size_bytes histogram"),
+    )
+});
+
+static HTTP_RESPONSE_SIZE_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "http_response_size_bytes",
+                "HTTP response body size in bytes",
+            )
+            .buckets(payload_size_buckets()),
+            &["method", "endpoint"],
+        )
+        .expect("valid http_response_size_bytes histogram"),
+    )
+});
+
+static HTTP_REQUEST_TIMEOUTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "http_request_timeouts_total",
+                "Total number of HTTP requests aborted by the request timeout",
+            ),
+            &["method", "endpoint"],
+        )
+        .expect("valid http_request_timeouts_total counter"),
+    )
+});
+
+static HTTP_REQUESTS_RATE_LIMITED_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "http_requests_rate_limited_total",
+                "Total number of HTTP requests rejected by the rate limiter",
+            ),
+            &["method", "endpoint"],
+        )
+        .expect("valid http_requests_rate_limited_total counter"),
+    )
+});
+
+static APP_ERRORS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "app_errors_total",
+                "Total number of application errors by kind",
+            ),
+            &["kind"],
+        )
+        .expect("valid app_errors_total counter"),
     )
-    .expect("failed to register http_requests_in_flight gauge")
 });
 
+static FIBONACCI_COMPUTATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "fibonacci_computation_seconds",
+                "Time spent computing fibonacci numbers, excluding request overhead",
+            )
+            .buckets(
+                // 1 µs to ~1 s in 4x steps; the iterative algorithm finishes in microseconds
+                exponential_buckets(0.000_001, 4.0, 11).expect("valid fibonacci buckets"),
+            ),
+            &["n_range"],
+        )
+        .expect("valid fibonacci_computation_seconds histogram"),
+    )
+});
+
+static LOG_LINES_DROPPED_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "log_lines_dropped_total",
+                "Log lines lost before reaching the log file, by reason",
+            ),
+            &["reason"],
+        )
+        .expect("valid log_lines_dropped_total counter"),
+    )
+});
+
+/// Queue-full drops already added to `log_lines_dropped_total`
+static SYNCED_QUEUE_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+static BUILD_INFO: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register(
+        IntGaugeVec::new(
+            Opts::new(
+                "build_info",
+                "Always 1; labels describe the running build for joins in dashboards",
+            ),
+            &["version", "rustc", "git_sha"],
+        )
+        .expect("valid build_info gauge"),
+    )
+});
+
+static USERS_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new("users_total", "Number of users currently registered")
+            .expect("valid users_total gauge"),
+    )
+});
+
+/// Default latency buckets, 5 ms to 5 s
+const DEFAULT_LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Default latency objective for `http_requests_slo_good_total`
+const DEFAULT_SLO_LATENCY_THRESHOLD_MS: u64 = 300;
+
+/// Latency objective from `SLO_LATENCY_THRESHOLD_MS`, or the default
+static SLO_LATENCY_THRESHOLD: Lazy<Duration> = Lazy::new(|| {
+    let mut errors = Vec::new();
+    let threshold_ms = crate::config::env_parse_or(
+        "SLO_LATENCY_THRESHOLD_MS",
+        DEFAULT_SLO_LATENCY_THRESHOLD_MS,
+        &mut errors,
+    );
+    for err in errors {
+        tracing::warn!("Ignoring invalid metrics setting: {err}");
+    }
+    Duration::from_millis(threshold_ms)
+});
+
+/// Whether a finished request counts as good for the SLO
+fn meets_slo(status: u16, duration: Duration) -> bool {
+    status < 500 && duration < *SLO_LATENCY_THRESHOLD
+}
+
+/// Latency buckets shared by the Prometheus histogram and its OTLP mirror
+static LATENCY_BUCKETS: Lazy<Vec<f64>> = Lazy::new(latency_buckets);
+
+/// Latency buckets from `HTTP_LATENCY_BUCKETS` (comma-separated seconds), or the defaults
+fn latency_buckets() -> Vec<f64> {
+    let Some(raw) = crate::config::env_string("HTTP_LATENCY_BUCKETS") else {
+        return DEFAULT_LATENCY_BUCKETS.to_vec();
+    };
+
+    parse_buckets(&raw).unwrap_or_else(|reason| {
+        let err = ConfigError {
+            var: "HTTP_LATENCY_BUCKETS",
+            value: raw,
+            reason,
+        };
+        tracing::warn!("Ignoring invalid metrics setting: {err}");
+        DEFAULT_LATENCY_BUCKETS.to_vec()
+    })
+}
+
+/// Parse a comma-separated list of positive, strictly increasing bucket bounds
+fn parse_buckets(raw: &str) -> Result<Vec<f64>, String> {
+    let buckets = raw
+        .split(',')
+        .map(|bound| {
+            let bound = bound.trim();
+            bound
+                .parse::<f64>()
+                .map_err(|err| format!("{bound:?}: {err}"))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+
+    if let Some(bound) = buckets
+        .iter()
+        .find(|bound| !bound.is_finite() || **bound <= 0.0)
+    {
+        return Err(format!("bucket {bound} must be a positive number"));
+    }
+
+    if buckets.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err("buckets must be sorted in strictly increasing order".to_string());
+    }
+
+    Ok(buckets)
+}
+
+/// 64 B to 1 MiB in 4x steps
+fn payload_size_buckets() -> Vec<f64> {
+    exponential_buckets(64.0, 4.0, 8).expect("valid payload size buckets")
+}
+
+/// Decrements the in-flight gauge when dropped, so it stays balanced even if the handler
+/// panics or the request future is dropped before completing.
+pub struct InFlightGuard {
+    gauge: IntGauge,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
+}
+
 /// Record that a request has started so we can capture concurrent request counts.
-pub fn track_request_start(method: &str, endpoint: &str) {
-    HTTP_REQUESTS_IN_FLIGHT
-        .with_label_values(&[method, endpoint])
-        .inc();
+///
+/// Keep the returned guard alive for the lifetime of the request.
+pub fn track_request_start(method: &str, endpoint: &str) -> InFlightGuard {
+    let gauge = HTTP_REQUESTS_IN_FLIGHT.with_label_values(&[method, endpoint]);
+    gauge.inc();
+    InFlightGuard { gauge }
+}
+
+/// OTLP mirrors of the request counter and latency histogram
+struct OtlpInstruments {
+    requests: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl OtlpInstruments {
+    /// The instruments reuse the Prometheus names and label dimensions, so dashboards can
+    /// switch between the scrape and OTLP paths without rewriting queries.
+    fn new(meter: &Meter) -> Self {
+        Self {
+            requests: meter
+                .u64_counter("http_requests_total")
+                .with_description("Total number of HTTP requests processed")
+                .init(),
+            duration: meter
+                .f64_histogram("http_request_duration_seconds")
+                .with_description("HTTP request latency in seconds")
+                .with_unit(Unit::new("s"))
+                .init(),
+        }
+    }
+
+    /// Mirror one finished request
+    fn record(&self, method: &str, endpoint: &str, status: &str, duration: Duration) {
+        let attributes = [
+            KeyValue::new("method", method.to_string()),
+            KeyValue::new("endpoint", endpoint.to_string()),
+            KeyValue::new("status", status.to_string()),
+        ];
+        self.requests.add(1, &attributes);
+        self.duration.record(duration.as_secs_f64(), &attributes);
+    }
+}
+
+/// Set when OTLP metrics export is enabled (`OTEL_METRICS_ENABLED`)
+static OTLP_INSTRUMENTS: OnceLock<OtlpInstruments> = OnceLock::new();
+
+/// Start mirroring request metrics into OpenTelemetry instruments created from `meter`.
+pub fn init_otlp_instruments(meter: &Meter) {
+    let _ = OTLP_INSTRUMENTS.set(OtlpInstruments::new(meter));
+}
+
+/// Aggregation for the OTLP pipeline: histograms use the Prometheus latency buckets
+///
+/// The SDK defaults (0–10000) suit milliseconds, not the seconds recorded here.
+pub struct LatencyAggregationSelector;
+
+impl AggregationSelector for LatencyAggregationSelector {
+    fn aggregation(&self, kind: InstrumentKind) -> Aggregation {
+        match kind {
+            InstrumentKind::Histogram => Aggregation::ExplicitBucketHistogram {
+                boundaries: LATENCY_BUCKETS.clone(),
+                record_min_max: true,
+            },
+            _ => DefaultAggregationSelector::new().aggregation(kind),
+        }
+    }
+}
+
+/// Latest traced observation in one latency bucket, exposed as an OpenMetrics exemplar
+#[derive(Clone, Debug)]
+struct Exemplar {
+    trace_id: String,
+    value: f64,
+}
+
+/// Exemplar slot for each latency bucket of one series
+type BucketExemplars = Vec<Option<Exemplar>>;
+
+/// Last exemplar per `http_request_duration_seconds` bucket (the final slot is `+Inf`),
+/// keyed by the series' label values in label-name order
+///
+/// The `prometheus` crate has no exemplar API, so they are kept here and written by
+/// `encode_openmetrics`. Label values are bounded, so this grows no larger than the histogram.
+static LATENCY_EXEMPLARS: Lazy<Mutex<HashMap<Vec<String>, BucketExemplars>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Label values of a series sorted by label name, as Prometheus orders them
+fn series_key<'a>(labels: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<String> {
+    let mut labels: Vec<_> = labels.into_iter().collect();
+    labels.sort_unstable_by_key(|(name, _)| *name);
+    labels
+        .into_iter()
+        .map(|(_, value)| value.to_string())
+        .collect()
+}
+
+/// Remember `trace_id` as the exemplar of the bucket `seconds` falls into
+fn record_latency_exemplar(key: Vec<String>, seconds: f64, trace_id: &str) {
+    let bucket = LATENCY_BUCKETS
+        .iter()
+        .position(|bound| seconds <= *bound)
+        .unwrap_or(LATENCY_BUCKETS.len());
+
+    let mut exemplars = LATENCY_EXEMPLARS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let buckets = exemplars
+        .entry(key)
+        .or_insert_with(|| vec![None; LATENCY_BUCKETS.len() + 1]);
+    buckets[bucket] = Some(Exemplar {
+        trace_id: trace_id.to_string(),
+        value: seconds,
+    });
 }
 
-/// Record request completion metrics (counter, latency histogram, in-flight gauge).
-pub fn track_request_result(method: &str, endpoint: &str, status: u16, duration: Duration) {
+/// Record request completion metrics (counter, latency histogram).
+///
+/// `trace_id` becomes the exemplar of the latency bucket the request fell into, shown on
+/// OpenMetrics scrapes so a latency outlier links straight to its trace.
+pub fn track_request_result(
+    method: &str,
+    endpoint: &str,
+    status: u16,
+    duration: Duration,
+    trace_id: Option<&str>,
+) {
     let status_label = status.to_string();
 
-    HTTP_REQUESTS_IN_FLIGHT
-        .with_label_values(&[method, endpoint])
-        .dec();
+    if let Some(trace_id) = trace_id {
+        let key = series_key([
+            ("method", method),
+            ("endpoint", endpoint),
+            ("status", status_label.as_str()),
+        ]);
+        record_latency_exemplar(key, duration.as_secs_f64(), trace_id);
+    }
 
     HTTP_REQUESTS_TOTAL
         .with_label_values(&[method, endpoint, &status_label])
@@ -57,11 +478,381 @@ pub fn track_request_result(method: &str, endpoint: &str, status: u16, duration:
     HTTP_REQUEST_DURATION_SECONDS
         .with_label_values(&[method, endpoint, &status_label])
         .observe(duration.as_secs_f64());
+
+    // Touched either way so the good/total ratio has a series from the first request
+    let slo_good = HTTP_REQUESTS_SLO_GOOD_TOTAL.with_label_values(&[method, endpoint]);
+    if meets_slo(status, duration) {
+        slo_good.inc();
+    }
+
+    if let Some(instruments) = OTLP_INSTRUMENTS.get() {
+        instruments.record(method, endpoint, &status_label, duration);
+    }
+}
+
+/// Record request/response body sizes; `None` (unknown or streaming length) is skipped.
+pub fn track_payload_sizes(
+    method: &str,
+    endpoint: &str,
+    request_size: Option<u64>,
+    response_size: Option<u64>,
+) {
+    if let Some(size) = request_size {
+        HTTP_REQUEST_SIZE_BYTES
+            .with_label_values(&[method, endpoint])
+            .observe(size as f64);
+    }
+
+    if let Some(size) = response_size {
+        HTTP_RESPONSE_SIZE_BYTES
+            .with_label_values(&[method, endpoint])
+            .observe(size as f64);
+    }
+}
+
+/// Record a request aborted by `TimeoutMiddleware`.
+pub fn track_request_timeout(method: &str, endpoint: &str) {
+    HTTP_REQUEST_TIMEOUTS_TOTAL
+        .with_label_values(&[method, endpoint])
+        .inc();
+}
+
+/// Record a request rejected by `RateLimitMiddleware`.
+pub fn track_rate_limited(method: &str, endpoint: &str) {
+    HTTP_REQUESTS_RATE_LIMITED_TOTAL
+        .with_label_values(&[method, endpoint])
+        .inc();
+}
+
+/// Record a log line the file writer failed to write (e.g. disk full)
+pub fn track_log_line_dropped() {
+    LOG_LINES_DROPPED_TOTAL
+        .with_label_values(&["write_error"])
+        .inc();
+}
+
+/// Catch `log_lines_dropped_total{reason="queue_full"}` up with the non-blocking writer
+///
+/// The writer only exposes a running total of lines dropped because its queue was full, so
+/// add whatever it dropped since the last scrape.
+fn sync_log_queue_drops() {
+    let dropped = crate::observability::dropped_log_lines();
+    let previous = SYNCED_QUEUE_DROPS.swap(dropped, Ordering::Relaxed);
+
+    LOG_LINES_DROPPED_TOTAL
+        .with_label_values(&["queue_full"])
+        .inc_by(dropped.saturating_sub(previous) as u64);
+    LOG_LINES_DROPPED_TOTAL.with_label_values(&["write_error"]);
+}
+
+/// Record an application error; `kind` is e.g. `validation`, `not_found`, `internal` or `timeout`.
+pub fn track_app_error(kind: &str) {
+    APP_ERRORS_TOTAL.with_label_values(&[kind]).inc();
+}
+
+/// Bucket `n` into a bounded label so each input value doesn't become its own series
+fn fibonacci_n_range(n: u32) -> &'static str {
+    match n {
+        0..=9 => "0-9",
+        10..=49 => "10-49",
+        50..=93 => "50-93",
+        _ => "94+",
+    }
+}
+
+/// Record the pure computation time of one fibonacci evaluation.
+pub fn track_fibonacci_computation(n: u32, duration: Duration) {
+    FIBONACCI_COMPUTATION_SECONDS
+        .with_label_values(&[fibonacci_n_range(n)])
+        .observe(duration.as_secs_f64());
+}
+
+/// Publish `build_info`; the git SHA and rustc version are captured by `build.rs`
+pub fn set_build_info() {
+    BUILD_INFO
+        .with_label_values(&[
+            env!("CARGO_PKG_VERSION"),
+            env!("RUSTC_VERSION"),
+            env!("GIT_SHA"),
+        ])
+        .set(1);
+}
+
+/// Business metric: current number of users in the store.
+pub fn set_users_total(count: usize) {
+    USERS_TOTAL.set(count as i64);
+}
+
+/// Sum of every series of a counter or gauge, across all label values
+fn series_total(collector: &impl Collector) -> u64 {
+    collector
+        .collect()
+        .iter()
+        .flat_map(|family| {
+            let metric_type = family.get_field_type();
+            family
+                .get_metric()
+                .iter()
+                .map(move |metric| match metric_type {
+                    MetricType::GAUGE => metric.get_gauge().get_value(),
+                    _ => metric.get_counter().get_value(),
+                })
+        })
+        .sum::<f64>() as u64
+}
+
+/// Log key counters as one structured `metrics.summary` event
+///
+/// Values are totals since startup (or the last `/metrics/reset`), summed over labels.
+pub fn log_summary() {
+    tracing::info!(
+        target: "metrics.summary",
+        http_requests = series_total(&*HTTP_REQUESTS_TOTAL),
+        http_requests_in_flight = series_total(&*HTTP_REQUESTS_IN_FLIGHT),
+        http_request_timeouts = series_total(&*HTTP_REQUEST_TIMEOUTS_TOTAL),
+        http_requests_rate_limited = series_total(&*HTTP_REQUESTS_RATE_LIMITED_TOTAL),
+        app_errors = series_total(&*APP_ERRORS_TOTAL),
+        log_lines_dropped = series_total(&*LOG_LINES_DROPPED_TOTAL),
+        users = USERS_TOTAL.get(),
+        "Metrics summary"
+    );
+}
+
+/// Call `log_summary` every `interval`, for setups without a Prometheus scraper
+///
+/// Runs until its task is aborted at shutdown.
+pub async fn log_summaries(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    // The first tick completes immediately; skip it so every summary covers a full interval
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        log_summary();
+    }
+}
+
+/// Whether the request carries `Authorization: Bearer <token>` (always true without a token)
+fn is_authorized(req: &HttpRequest, token: Option<&str>) -> bool {
+    token.is_none_or(|expected| has_bearer_token(req, expected))
+}
+
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Whether the scraper asked for OpenMetrics in its `Accept` header
+fn wants_openmetrics(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/openmetrics-text"))
+}
+
+/// Encode metric families in the OpenMetrics text format
+///
+/// The `prometheus` crate only ships the classic text encoder. The differences handled here:
+/// counter families are named without their `_total` suffix (samples keep it), untyped
+/// metrics are `unknown`, special floats use `+Inf`/`NaN`, and the output ends with `# EOF`.
+fn encode_openmetrics(metric_families: &[MetricFamily]) -> String {
+    let mut out = String::with_capacity(8192);
+
+    for family in metric_families {
+        let metric_type = family.get_field_type();
+        let name = match metric_type {
+            MetricType::COUNTER => family
+                .get_name()
+                .strip_suffix("_total")
+                .unwrap_or(family.get_name()),
+            _ => family.get_name(),
+        };
+        let type_name = match metric_type {
+            MetricType::COUNTER => "counter",
+            MetricType::GAUGE => "gauge",
+            MetricType::HISTOGRAM => "histogram",
+            MetricType::SUMMARY => "summary",
+            MetricType::UNTYPED => "unknown",
+        };
+
+        let help = family.get_help();
+        if !help.is_empty() {
+            let help = escape_openmetrics(help);
+            out.push_str(&format!("# HELP {name} {help}\n"));
+        }
+        out.push_str(&format!("# TYPE {name} {type_name}\n"));
+
+        for metric in family.get_metric() {
+            let labels = metric.get_label();
+
+            match metric_type {
+                MetricType::COUNTER => {
+                    let value = metric.get_counter().get_value();
+                    write_sample(&mut out, &format!("{name}_total"), labels, None, value);
+                }
+                MetricType::GAUGE => {
+                    write_sample(&mut out, name, labels, None, metric.get_gauge().get_value());
+                }
+                MetricType::UNTYPED => {
+                    write_sample(
+                        &mut out,
+                        name,
+                        labels,
+                        None,
+                        metric.get_untyped().get_value(),
+                    );
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let bucket_name = format!("{name}_bucket");
+                    let exemplars = if name == "http_request_duration_seconds" {
+                        let key = series_key(
+                            labels
+                                .iter()
+                                .map(|pair| (pair.get_name(), pair.get_value())),
+                        );
+                        LATENCY_EXEMPLARS
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .get(&key)
+                            .cloned()
+                            .unwrap_or_default()
+                    } else {
+                        Vec::new()
+                    };
+
+                    for (index, bucket) in histogram.get_bucket().iter().enumerate() {
+                        let bound = format_value(bucket.get_upper_bound());
+                        let count = bucket.get_cumulative_count() as f64;
+                        write_exemplar_sample(
+                            &mut out,
+                            &bucket_name,
+                            labels,
+                            Some(("le", &bound)),
+                            count,
+                            exemplars.get(index).and_then(Option::as_ref),
+                        );
+                    }
+                    // The `prometheus` crate never stores the `+Inf` bucket; it equals the count
+                    let count = histogram.get_sample_count() as f64;
+                    write_exemplar_sample(
+                        &mut out,
+                        &bucket_name,
+                        labels,
+                        Some(("le", "+Inf")),
+                        count,
+                        exemplars.last().and_then(Option::as_ref),
+                    );
+
+                    let sum = histogram.get_sample_sum();
+                    write_sample(&mut out, &format!("{name}_sum"), labels, None, sum);
+                    write_sample(&mut out, &format!("{name}_count"), labels, None, count);
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+
+                    for quantile in summary.get_quantile() {
+                        let label = format_value(quantile.get_quantile());
+                        let value = quantile.get_value();
+                        write_sample(&mut out, name, labels, Some(("quantile", &label)), value);
+                    }
+
+                    let sum = summary.get_sample_sum();
+                    let count = summary.get_sample_count() as f64;
+                    write_sample(&mut out, &format!("{name}_sum"), labels, None, sum);
+                    write_sample(&mut out, &format!("{name}_count"), labels, None, count);
+                }
+            }
+        }
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+/// Append one `name{labels} value` line
+fn write_sample(
+    out: &mut String,
+    name: &str,
+    labels: &[LabelPair],
+    extra_label: Option<(&str, &str)>,
+    value: f64,
+) {
+    write_exemplar_sample(out, name, labels, extra_label, value, None);
+}
+
+/// Append one sample line, followed by `# {trace_id="..."} value` when it has an exemplar
+fn write_exemplar_sample(
+    out: &mut String,
+    name: &str,
+    labels: &[LabelPair],
+    extra_label: Option<(&str, &str)>,
+    value: f64,
+    exemplar: Option<&Exemplar>,
+) {
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|pair| (pair.get_name(), pair.get_value()))
+        .chain(extra_label)
+        .map(|(name, value)| format!("{name}=\"{}\"", escape_openmetrics(value)))
+        .collect();
+
+    out.push_str(name);
+    if !pairs.is_empty() {
+        out.push_str(&format!("{{{}}}", pairs.join(",")));
+    }
+    out.push_str(&format!(" {}", format_value(value)));
+    if let Some(Exemplar { trace_id, value }) = exemplar {
+        let trace_id = escape_openmetrics(trace_id);
+        out.push_str(&format!(
+            " # {{trace_id=\"{trace_id}\"}} {}",
+            format_value(*value)
+        ));
+    }
+    out.push('\n');
+}
+
+/// Escape backslashes, quotes and newlines in label values and help text
+fn escape_openmetrics(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// OpenMetrics spelling of a float (`+Inf`, `-Inf`, `NaN` for the special values)
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
 }
 
 /// Expose Prometheus metrics via `/metrics`.
-pub async fn metrics_handler() -> impl Responder {
+///
+/// On Linux the default registry also carries `process_*` metrics (CPU seconds, resident
+/// memory, open fds) from the `prometheus` crate's process collector.
+///
+/// Scrapers sending `Accept: application/openmetrics-text` get the OpenMetrics exposition
+/// format; everything else gets the classic Prometheus text format.
+///
+/// When `METRICS_TOKEN` is set, scrapers must send it as a bearer token or receive a 401.
+pub async fn metrics_handler(req: HttpRequest) -> impl Responder {
+    if !is_authorized(&req, METRICS_TOKEN.as_deref()) {
+        tracing::warn!("Rejected unauthorized metrics scrape");
+        return unauthorized();
+    }
+
+    sync_log_queue_drops();
     let metric_families = prometheus::gather();
+
+    if wants_openmetrics(&req) {
+        return HttpResponse::Ok()
+            .content_type(OPENMETRICS_CONTENT_TYPE)
+            .body(encode_openmetrics(&metric_families));
+    }
+
     let mut buffer = Vec::with_capacity(8192);
     let encoder = TextEncoder::new();
 
@@ -74,3 +865,421 @@ pub async fn metrics_handler() -> impl Responder {
         .content_type(encoder.format_type())
         .body(buffer)
 }
+
+/// Zero the request counters and histograms via `POST /metrics/reset`.
+///
+/// Intended for test environments, e.g. between load-test iterations; do not expose it in
+/// production, where resets show up as counter drops in every rate() query. Only mounted when
+/// `DEBUG_ENDPOINTS` is on, and unlike scraping it always requires the `METRICS_TOKEN` bearer
+/// token: without a configured token every reset is refused.
+pub async fn reset_metrics_handler(req: HttpRequest) -> impl Responder {
+    if !is_reset_authorized(&req, METRICS_TOKEN.as_deref()) {
+        tracing::warn!("Rejected unauthorized metrics reset");
+        return unauthorized();
+    }
+
+    reset_metrics();
+    tracing::warn!("Metrics reset");
+
+    HttpResponse::NoContent().finish()
+}
+
+/// Resets are destructive, so a missing token denies instead of opening the endpoint
+fn is_reset_authorized(req: &HttpRequest, token: Option<&str>) -> bool {
+    token.is_some_and(|expected| has_bearer_token(req, expected))
+}
+
+/// Gauges describing current state (`http_requests_in_flight`, `users_total`) are left alone
+fn reset_metrics() {
+    HTTP_REQUESTS_TOTAL.reset();
+    HTTP_REQUEST_DURATION_SECONDS.reset();
+    HTTP_REQUESTS_SLO_GOOD_TOTAL.reset();
+    HTTP_REQUEST_SIZE_BYTES.reset();
+    HTTP_RESPONSE_SIZE_BYTES.reset();
+    HTTP_REQUEST_TIMEOUTS_TOTAL.reset();
+    HTTP_REQUESTS_RATE_LIMITED_TOTAL.reset();
+    APP_ERRORS_TOTAL.reset();
+    FIBONACCI_COMPUTATION_SECONDS.reset();
+    LOG_LINES_DROPPED_TOTAL.reset();
+    LATENCY_EXEMPLARS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{TestTracing, event_attribute, lock_metrics_blocking, metric_value};
+    use actix_web::http::header::AUTHORIZATION;
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry_sdk::metrics::{
+        Pipeline,
+        data::{Histogram, ResourceMetrics, Sum, Temporality},
+        reader::{MetricProducer, MetricReader, TemporalitySelector},
+    };
+    use tracing::Instrument as _;
+
+    #[test]
+    fn payload_sizes_observed_when_known() {
+        let _metrics = lock_metrics_blocking();
+        let labels = ["POST", "/test/payload-sizes"];
+        track_payload_sizes(labels[0], labels[1], Some(512), None);
+
+        let request = HTTP_REQUEST_SIZE_BYTES.with_label_values(&labels);
+        assert_eq!(request.get_sample_count(), 1);
+        assert_eq!(request.get_sample_sum(), 512.0);
+        let response = HTTP_RESPONSE_SIZE_BYTES.with_label_values(&labels);
+        assert_eq!(response.get_sample_count(), 0, "unknown sizes are skipped");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn process_metrics_gathered_on_linux() {
+        let names: Vec<String> = prometheus::gather()
+            .iter()
+            .map(|family| family.get_name().to_string())
+            .collect();
+
+        assert!(
+            names
+                .iter()
+                .any(|name| name == "process_resident_memory_bytes"),
+            "{names:?}"
+        );
+        assert!(names.iter().any(|name| name == "process_cpu_seconds_total"));
+    }
+
+    fn with_authorization(value: Option<&str>) -> HttpRequest {
+        let mut req = actix_web::test::TestRequest::get();
+        if let Some(value) = value {
+            req = req.insert_header((AUTHORIZATION, value));
+        }
+        req.to_http_request()
+    }
+
+    #[test]
+    fn bearer_token_is_required_when_configured() {
+        let token = Some("s3cret");
+
+        assert!(is_authorized(
+            &with_authorization(Some("Bearer s3cret")),
+            token
+        ));
+        for value in [
+            None,
+            Some("Bearer wrong"),
+            Some("s3cret"),
+            Some("Basic s3cret"),
+        ] {
+            assert!(
+                !is_authorized(&with_authorization(value), token),
+                "{value:?}"
+            );
+        }
+
+        // Without a token, scraping stays open
+        assert!(is_authorized(&with_authorization(None), None));
+    }
+
+    #[test]
+    fn custom_buckets_parse_in_order() {
+        assert_eq!(
+            parse_buckets("0.01, 0.1,1,10").unwrap(),
+            vec![0.01, 0.1, 1.0, 10.0]
+        );
+    }
+
+    #[test]
+    fn unsorted_or_invalid_buckets_are_rejected() {
+        assert!(parse_buckets("0.1,0.05,1").is_err());
+        assert!(parse_buckets("0.1,0.1").is_err());
+        assert!(parse_buckets("0.1,-1").is_err());
+        assert!(parse_buckets("0.1,fast").is_err());
+    }
+
+    #[test]
+    fn reset_requires_a_configured_token() {
+        let anonymous = actix_web::test::TestRequest::get().to_http_request();
+        let bearer = actix_web::test::TestRequest::get()
+            .insert_header(("Authorization", "Bearer s3cret"))
+            .to_http_request();
+
+        assert!(!is_reset_authorized(&anonymous, None));
+        assert!(!is_reset_authorized(&bearer, None));
+        assert!(!is_reset_authorized(&anonymous, Some("s3cret")));
+        assert!(is_reset_authorized(&bearer, Some("s3cret")));
+    }
+
+    #[test]
+    fn reset_zeroes_request_counters() {
+        let _metrics = lock_metrics_blocking();
+        let labels = [
+            ("method", "GET"),
+            ("endpoint", "/reset-test"),
+            ("status", "200"),
+        ];
+        HTTP_REQUESTS_TOTAL
+            .with_label_values(&["GET", "/reset-test", "200"])
+            .inc();
+        APP_ERRORS_TOTAL.with_label_values(&["reset_test"]).inc();
+        assert_eq!(metric_value("http_requests_total", &labels), 1.0);
+
+        reset_metrics();
+
+        assert_eq!(metric_value("http_requests_total", &labels), 0.0);
+        assert_eq!(
+            metric_value("app_errors_total", &[("kind", "reset_test")]),
+            0.0
+        );
+    }
+
+    #[actix_web::test]
+    async fn metrics_format_follows_accept_header() {
+        for (accept, content_type) in [
+            (
+                "application/openmetrics-text; version=1.0.0",
+                OPENMETRICS_CONTENT_TYPE,
+            ),
+            ("text/plain", prometheus::TEXT_FORMAT),
+        ] {
+            let req = actix_web::test::TestRequest::get()
+                .insert_header((actix_web::http::header::ACCEPT, accept))
+                .to_http_request();
+            let res = metrics_handler(req.clone())
+                .await
+                .respond_to(&req)
+                .map_into_boxed_body();
+
+            assert_eq!(res.status(), actix_web::http::StatusCode::OK);
+            assert_eq!(
+                res.headers()
+                    .get(actix_web::http::header::CONTENT_TYPE)
+                    .unwrap(),
+                content_type
+            );
+            let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+            let body = std::str::from_utf8(&body).unwrap();
+            assert_eq!(
+                body.ends_with("# EOF\n"),
+                content_type == OPENMETRICS_CONTENT_TYPE
+            );
+        }
+    }
+
+    fn test_counter() -> IntCounterVec {
+        IntCounterVec::new(
+            Opts::new("test_double_registration_total", "Registered twice"),
+            &["kind"],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn double_registration_reuses_the_exported_collector() {
+        let _metrics = lock_metrics_blocking();
+        // Registered behind the helper's back, as another module or test might
+        prometheus::register(Box::new(test_counter())).unwrap();
+
+        let first = register(test_counter());
+        let second = register(test_counter());
+        first.with_label_values(&["a"]).inc();
+        second.with_label_values(&["a"]).inc();
+
+        assert_eq!(
+            metric_value("test_double_registration_total", &[("kind", "a")]),
+            2.0
+        );
+    }
+
+    /// `ManualReader` handle kept by the test after the provider takes ownership of the reader
+    #[derive(Clone, Debug, Default)]
+    struct SharedReader(std::sync::Arc<opentelemetry_sdk::metrics::ManualReader>);
+
+    impl AggregationSelector for SharedReader {
+        fn aggregation(&self, kind: InstrumentKind) -> Aggregation {
+            LatencyAggregationSelector.aggregation(kind)
+        }
+    }
+
+    impl TemporalitySelector for SharedReader {
+        fn temporality(&self, kind: InstrumentKind) -> Temporality {
+            self.0.temporality(kind)
+        }
+    }
+
+    impl MetricReader for SharedReader {
+        fn register_pipeline(&self, pipeline: std::sync::Weak<Pipeline>) {
+            self.0.register_pipeline(pipeline);
+        }
+
+        fn register_producer(&self, producer: Box<dyn MetricProducer>) {
+            self.0.register_producer(producer);
+        }
+
+        fn collect(&self, rm: &mut ResourceMetrics) -> opentelemetry::metrics::Result<()> {
+            self.0.collect(rm)
+        }
+
+        fn force_flush(&self, cx: &opentelemetry::Context) -> opentelemetry::metrics::Result<()> {
+            self.0.force_flush(cx)
+        }
+
+        fn shutdown(&self) -> opentelemetry::metrics::Result<()> {
+            self.0.shutdown()
+        }
+    }
+
+    #[test]
+    fn request_is_mirrored_to_otlp() {
+        let reader = SharedReader::default();
+        let provider = opentelemetry_sdk::metrics::MeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        // A local instrument set, so the process-wide OTLP mirror stays unset
+        let instruments = OtlpInstruments::new(&provider.meter("test"));
+
+        instruments.record("GET", "/test/otlp", "200", Duration::from_millis(5));
+
+        let mut collected = ResourceMetrics {
+            resource: opentelemetry_sdk::Resource::empty(),
+            scope_metrics: Vec::new(),
+        };
+        reader.collect(&mut collected).unwrap();
+        let metrics: Vec<_> = collected
+            .scope_metrics
+            .iter()
+            .flat_map(|scope| &scope.metrics)
+            .collect();
+        let is_test_endpoint = |attributes: &opentelemetry_sdk::AttributeSet| {
+            attributes
+                .iter()
+                .any(|(key, value)| key.as_str() == "endpoint" && value.as_str() == "/test/otlp")
+        };
+
+        let requests = metrics
+            .iter()
+            .find(|metric| metric.name == "http_requests_total")
+            .and_then(|metric| metric.data.as_any().downcast_ref::<Sum<u64>>())
+            .unwrap();
+        let point = requests
+            .data_points
+            .iter()
+            .find(|point| is_test_endpoint(&point.attributes))
+            .unwrap();
+        assert_eq!(point.value, 1);
+
+        let duration = metrics
+            .iter()
+            .find(|metric| metric.name == "http_request_duration_seconds")
+            .and_then(|metric| metric.data.as_any().downcast_ref::<Histogram<f64>>())
+            .unwrap();
+        let point = duration
+            .data_points
+            .iter()
+            .find(|point| is_test_endpoint(&point.attributes))
+            .unwrap();
+        assert_eq!(point.count, 1);
+        assert_eq!(point.bounds, *LATENCY_BUCKETS);
+    }
+
+    #[test]
+    fn slo_counts_fast_successes_as_good() {
+        let _metrics = lock_metrics_blocking();
+        let slow = *SLO_LATENCY_THRESHOLD + Duration::from_millis(1);
+
+        track_request_result("GET", "/test/slo-fast", 200, Duration::from_millis(1), None);
+        track_request_result("GET", "/test/slo-slow", 500, slow, None);
+
+        for (endpoint, good) in [("/test/slo-fast", 1.0), ("/test/slo-slow", 0.0)] {
+            let labels = [("method", "GET"), ("endpoint", endpoint)];
+            assert_eq!(
+                metric_value("http_requests_total", &labels),
+                1.0,
+                "{endpoint}"
+            );
+            assert_eq!(
+                metric_value("http_requests_slo_good_total", &labels),
+                good,
+                "{endpoint}"
+            );
+        }
+    }
+
+    #[test]
+    fn build_info_carries_the_version() {
+        let _metrics = lock_metrics_blocking();
+        set_build_info();
+
+        let families = prometheus::gather();
+        let build_info = families
+            .iter()
+            .find(|family| family.get_name() == "build_info")
+            .expect("build_info is registered");
+        let metric = &build_info.get_metric()[0];
+
+        assert_eq!(metric.get_gauge().get_value(), 1.0);
+        assert!(
+            metric
+                .get_label()
+                .iter()
+                .any(|pair| pair.get_name() == "version"
+                    && pair.get_value() == env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[actix_web::test]
+    async fn summary_is_logged_each_tick() {
+        let tracing = TestTracing::install();
+        let span = tracing::info_span!("summaries");
+        let task = actix_web::rt::spawn(log_summaries(Duration::from_millis(20)).instrument(span));
+
+        // The immediate first tick is skipped, so nothing is logged before one interval
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        task.abort();
+        let _ = task.await;
+
+        let span = &tracing.spans_named("summaries")[0];
+        let summaries: Vec<_> = span
+            .events
+            .iter()
+            .filter(|event| event.name == "Metrics summary")
+            .collect();
+        assert!(!summaries.is_empty());
+        for key in ["http_requests", "app_errors", "users"] {
+            assert!(event_attribute(summaries[0], key).is_some(), "{key}");
+        }
+    }
+
+    #[test]
+    fn exemplar_follows_its_bucket() {
+        let _metrics = lock_metrics_blocking();
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+        track_request_result(
+            "GET",
+            "/test/exemplar",
+            200,
+            Duration::from_millis(30),
+            Some(trace_id),
+        );
+
+        let body = encode_openmetrics(&prometheus::gather());
+        let bucket = |le| {
+            body.lines()
+                .find(|line| {
+                    line.starts_with("http_request_duration_seconds_bucket{")
+                        && line.contains("endpoint=\"/test/exemplar\"")
+                        && line.contains(&format!("le=\"{le}\""))
+                })
+                .unwrap()
+                .to_string()
+        };
+        assert!(
+            bucket("0.05").ends_with(&format!(" 1 # {{trace_id=\"{trace_id}\"}} 0.03")),
+            "{body}"
+        );
+        assert!(!bucket("0.025").contains('#'));
+        assert!(!bucket("+Inf").contains('#'));
+    }
+}
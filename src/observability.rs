@@ -9,35 +9,856 @@
 /// - Spans: Represent a unit of work (request, database query, etc.)
 /// - Traces: Collection of spans that represent a complete operation
 /// - Metrics: Quantitative measurements
-/// - Logs: Textual information about events
-use std::sync::OnceLock;
+/// - Logs: Textual information about events, optionally also exported as OTLP log records
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+    sync::{
+        Mutex, OnceLock, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 
-use tracing::info;
-use tracing_appender::non_blocking::WorkerGuard;
-use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+use actix_web::http::Uri;
+use opentelemetry::logs::LogError;
+use opentelemetry::{
+    Context, Key, KeyValue, OrderMap, Value, global,
+    metrics::{MeterProvider as _, MetricsError},
+    propagation::TextMapPropagator,
+    trace::{
+        Link, SamplingDecision, SamplingResult, SpanKind, TraceContextExt, TraceError, TraceId,
+        TracerProvider as _,
+    },
+};
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+use opentelemetry_otlp::{
+    LogExporterBuilder, MetricsExporterBuilder, SpanExporterBuilder, WithExportConfig,
+};
+use opentelemetry_sdk::{
+    Resource,
+    export::trace::SpanExporter,
+    logs::{self as sdklogs, LoggerProvider},
+    metrics::MeterProvider,
+    propagation::{BaggagePropagator, TextMapCompositePropagator, TraceContextPropagator},
+    runtime,
+    trace::{self as sdktrace, BatchConfig, BatchSpanProcessor, Sampler, ShouldSample},
+};
+use opentelemetry_zipkin::B3Encoding;
+use tokio::net::TcpStream;
+use tracing::{info, warn};
+use tracing_appender::{
+    non_blocking::{ErrorCounter, NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry, filter::filter_fn, layer::SubscriberExt, reload,
+    util::SubscriberInitExt,
+};
+use uuid::Uuid;
 
-static FILE_GUARD: OnceLock<WorkerGuard> = OnceLock::new();
+/// Flush guard for the non-blocking file writer; taken and dropped on shutdown
+static FILE_GUARD: Mutex<Option<WorkerGuard>> = Mutex::new(None);
+
+/// Lines the non-blocking file writer dropped because its queue was full
+static FILE_DROP_COUNTER: OnceLock<ErrorCounter> = OnceLock::new();
+
+/// Set once `setup_telemetry` has finished; drives the readiness probe
+static TELEMETRY_READY: AtomicBool = AtomicBool::new(false);
+
+/// Result of the latest collector connectivity probe
+static OTLP_REACHABLE: AtomicBool = AtomicBool::new(false);
+
+/// Whether an unreachable collector fails readiness (`OTEL_REQUIRED_FOR_READINESS`)
+static OTLP_REQUIRED: AtomicBool = AtomicBool::new(false);
+
+/// OTLP meter provider, set when `OTEL_METRICS_ENABLED=true`; flushed on shutdown
+static METER_PROVIDER: OnceLock<MeterProvider> = OnceLock::new();
+
+/// OTLP logger provider, set when `OTEL_LOGS_ENABLED=true`; taken and shut down on exit
+///
+/// The bridge layer's logger only holds a weak reference, so this keeps the provider alive.
+static LOGGER_PROVIDER: Mutex<Option<LoggerProvider>> = Mutex::new(None);
+
+/// Event targets never bridged to OTLP logs: the exporter's own transport stack and error
+/// reports, whose events would otherwise be exported through itself
+const UNBRIDGED_LOG_TARGETS: &[&str] =
+    &["h2", "hyper", "opentelemetry", "reqwest", "tonic", "tower"];
+
+/// `service.instance.id`, resolved once so every resource built in this process agrees
+static SERVICE_INSTANCE_ID: OnceLock<String> = OnceLock::new();
+
+/// Handle for swapping the active `EnvFilter` at runtime
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+use crate::{
+    config::{ConfigError, env_parse_or, env_string},
+    log_context::{RequestIdLayer, WithLogContext},
+    metrics,
+};
+
+const DEFAULT_ENVIRONMENT: &str = "development";
+const DEFAULT_LOG_DIR: &str = "logs";
+const DEFAULT_LOG_FILE: &str = "app.log";
+const DEFAULT_OTLP_GRPC_ENDPOINT: &str = "http://localhost:4317";
+const DEFAULT_OTLP_HTTP_ENDPOINT: &str = "http://localhost:4318";
+const DEFAULT_OTLP_TIMEOUT_MS: u64 = 10_000;
+const DEFAULT_MAX_QUEUE_SIZE: usize = 2048;
+/// Deadline for one collector connectivity probe
+const OTLP_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Delay between probes while the collector is unreachable
+const OTLP_REPROBE_INTERVAL: Duration = Duration::from_secs(30);
+/// Probes and scrapes, which would otherwise flood traces with noise
+const DEFAULT_UNTRACED_PATHS: &str = "/healthz,/readyz,/metrics";
+/// Span attribute holding the request path, recorded by `RequestIdMiddleware`
+const PATH_ATTRIBUTE: &str = "path";
+
+/// Formatter used for stdout logs (`LOG_FORMAT`); the file layer is always JSON for Promtail
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum LogFormat {
+    #[default]
+    Json,
+    Pretty,
+    Compact,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "pretty" => Ok(Self::Pretty),
+            "compact" => Ok(Self::Compact),
+            _ => Err("expected one of json, pretty, compact".to_string()),
+        }
+    }
+}
+
+/// OTLP transport (`OTEL_EXPORTER_OTLP_PROTOCOL`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum OtlpProtocol {
+    #[default]
+    Grpc,
+    HttpProtobuf,
+}
+
+impl OtlpProtocol {
+    fn default_endpoint(self) -> &'static str {
+        match self {
+            Self::Grpc => DEFAULT_OTLP_GRPC_ENDPOINT,
+            Self::HttpProtobuf => DEFAULT_OTLP_HTTP_ENDPOINT,
+        }
+    }
+}
+
+impl FromStr for OtlpProtocol {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "grpc" => Ok(Self::Grpc),
+            "http/protobuf" => Ok(Self::HttpProtobuf),
+            _ => Err("expected one of grpc, http/protobuf".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for OtlpProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Grpc => "grpc",
+            Self::HttpProtobuf => "http/protobuf",
+        })
+    }
+}
+
+/// Span exporter selectable through `OTEL_EXPORTERS`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TraceExporter {
+    /// Batched export to the OTLP collector
+    Otlp,
+    /// Pretty-printed JSON on stdout, for local debugging
+    Stdout,
+}
+
+impl FromStr for TraceExporter {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "otlp" => Ok(Self::Otlp),
+            "stdout" => Ok(Self::Stdout),
+            _ => Err("expected one of otlp, stdout".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for TraceExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Otlp => "otlp",
+            Self::Stdout => "stdout",
+        })
+    }
+}
+
+/// Context propagation format selectable through `OTEL_PROPAGATORS`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PropagatorKind {
+    /// W3C Trace Context (`traceparent`/`tracestate`)
+    TraceContext,
+    /// W3C Baggage (`baggage`)
+    Baggage,
+    /// Zipkin B3 single header (`b3`); opentelemetry-zipkin 0.18 injects the multi headers too
+    B3,
+    /// Zipkin B3 multi headers (`X-B3-TraceId`, `X-B3-SpanId`, ...)
+    B3Multi,
+}
+
+impl PropagatorKind {
+    fn propagator(self) -> Box<dyn TextMapPropagator + Send + Sync> {
+        match self {
+            Self::TraceContext => Box::new(TraceContextPropagator::new()),
+            Self::Baggage => Box::new(BaggagePropagator::new()),
+            Self::B3 => Box::new(opentelemetry_zipkin::Propagator::with_encoding(
+                B3Encoding::SingleHeader,
+            )),
+            Self::B3Multi => Box::new(opentelemetry_zipkin::Propagator::with_encoding(
+                B3Encoding::MultipleHeader,
+            )),
+        }
+    }
+}
+
+impl FromStr for PropagatorKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "tracecontext" => Ok(Self::TraceContext),
+            "baggage" => Ok(Self::Baggage),
+            "b3" => Ok(Self::B3),
+            "b3multi" => Ok(Self::B3Multi),
+            _ => Err("expected one of tracecontext, baggage, b3, b3multi".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for PropagatorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::TraceContext => "tracecontext",
+            Self::Baggage => "baggage",
+            Self::B3 => "b3",
+            Self::B3Multi => "b3multi",
+        })
+    }
+}
+
+/// Telemetry settings read from standard `OTEL_*` environment variables
+struct TelemetryConfig {
+    /// Logical service name (`OTEL_SERVICE_NAME`, defaults to the crate name)
+    service_name: String,
+    /// Deployment environment such as `staging` or `production` (`APP_ENV`)
+    environment: String,
+    /// Directory for the JSON log file scraped by Promtail (`LOG_DIR`)
+    log_dir: String,
+    /// Log file name inside `log_dir` (`LOG_FILE`)
+    log_file: String,
+    /// Stdout log formatter (`LOG_FORMAT`)
+    log_format: LogFormat,
+    /// Whether to log to stdout as well as the file (`LOG_STDOUT`, default true)
+    log_stdout: bool,
+    /// Add `trace_id`/`span_id` to JSON log lines (`LOG_TRACE_CONTEXT`, default true)
+    log_trace_context: bool,
+    /// Span exporters to register (`OTEL_EXPORTERS`, comma-separated, default `otlp`)
+    exporters: Vec<TraceExporter>,
+    /// Context propagation formats, applied in order (`OTEL_PROPAGATORS`, comma-separated,
+    /// default `tracecontext,baggage`)
+    propagators: Vec<PropagatorKind>,
+    /// OTLP transport (`OTEL_EXPORTER_OTLP_PROTOCOL`, `grpc` or `http/protobuf`)
+    otlp_protocol: OtlpProtocol,
+    /// OTLP collector endpoint (`OTEL_EXPORTER_OTLP_ENDPOINT`, default port depends on protocol)
+    otlp_endpoint: String,
+    /// Fraction of new traces to sample (`OTEL_TRACES_SAMPLER_ARG`, 0.0–1.0)
+    sampling_ratio: f64,
+    /// Per-export deadline (`OTEL_EXPORTER_OTLP_TIMEOUT`, milliseconds)
+    export_timeout: Duration,
+    /// Spans buffered before new ones are dropped (`OTEL_BSP_MAX_QUEUE_SIZE`)
+    max_queue_size: usize,
+    /// Mirror request metrics to the collector over OTLP (`OTEL_METRICS_ENABLED`, default false)
+    metrics_enabled: bool,
+    /// Export `tracing` events as OTLP log records (`OTEL_LOGS_ENABLED`, default false)
+    logs_enabled: bool,
+    /// Request paths never traced (`OTEL_UNTRACED_PATHS`, comma-separated)
+    untraced_paths: Vec<String>,
+    /// Report not ready while the collector is unreachable (`OTEL_REQUIRED_FOR_READINESS`)
+    required_for_readiness: bool,
+}
+
+impl TelemetryConfig {
+    /// Read settings, falling back to defaults for invalid values
+    ///
+    /// Errors are collected rather than logged because the subscriber isn't installed yet.
+    fn from_env(errors: &mut Vec<ConfigError>) -> Self {
+        let service_name =
+            env_string("OTEL_SERVICE_NAME").unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string());
+        let environment = env_string("APP_ENV").unwrap_or_else(|| DEFAULT_ENVIRONMENT.to_string());
+        let log_dir = env_string("LOG_DIR").unwrap_or_else(|| DEFAULT_LOG_DIR.to_string());
+        let log_file = env_string("LOG_FILE").unwrap_or_else(|| DEFAULT_LOG_FILE.to_string());
+        let log_format = env_parse_or("LOG_FORMAT", LogFormat::default(), errors);
+        let log_stdout = env_parse_or("LOG_STDOUT", true, errors);
+        let log_trace_context = env_parse_or("LOG_TRACE_CONTEXT", true, errors);
+
+        let exporters = env_list("OTEL_EXPORTERS", &[TraceExporter::Otlp], errors);
+        let propagators = env_list(
+            "OTEL_PROPAGATORS",
+            &[PropagatorKind::TraceContext, PropagatorKind::Baggage],
+            errors,
+        );
+
+        let otlp_protocol = env_parse_or(
+            "OTEL_EXPORTER_OTLP_PROTOCOL",
+            OtlpProtocol::default(),
+            errors,
+        );
+        let otlp_endpoint = env_string("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .unwrap_or_else(|| otlp_protocol.default_endpoint().to_string());
+
+        let mut sampling_ratio = env_parse_or("OTEL_TRACES_SAMPLER_ARG", 1.0, errors);
+        if !(0.0..=1.0).contains(&sampling_ratio) {
+            errors.push(ConfigError {
+                var: "OTEL_TRACES_SAMPLER_ARG",
+                value: sampling_ratio.to_string(),
+                reason: "ratio must be between 0.0 and 1.0".to_string(),
+            });
+            sampling_ratio = 1.0;
+        }
+
+        let export_timeout = Duration::from_millis(env_parse_or(
+            "OTEL_EXPORTER_OTLP_TIMEOUT",
+            DEFAULT_OTLP_TIMEOUT_MS,
+            errors,
+        ));
+
+        let mut max_queue_size =
+            env_parse_or("OTEL_BSP_MAX_QUEUE_SIZE", DEFAULT_MAX_QUEUE_SIZE, errors);
+        if max_queue_size == 0 {
+            errors.push(ConfigError {
+                var: "OTEL_BSP_MAX_QUEUE_SIZE",
+                value: max_queue_size.to_string(),
+                reason: "queue size must be positive".to_string(),
+            });
+            max_queue_size = DEFAULT_MAX_QUEUE_SIZE;
+        }
+
+        let metrics_enabled = env_parse_or("OTEL_METRICS_ENABLED", false, errors);
+        let logs_enabled = env_parse_or("OTEL_LOGS_ENABLED", false, errors);
+
+        let untraced_paths = env_string("OTEL_UNTRACED_PATHS")
+            .unwrap_or_else(|| DEFAULT_UNTRACED_PATHS.to_string())
+            .split(',')
+            .map(|path| path.trim().to_string())
+            .filter(|path| !path.is_empty())
+            .collect();
+
+        let required_for_readiness = env_parse_or("OTEL_REQUIRED_FOR_READINESS", false, errors);
+
+        Self {
+            service_name,
+            environment,
+            log_dir,
+            log_file,
+            log_format,
+            log_stdout,
+            log_trace_context,
+            exporters,
+            propagators,
+            otlp_protocol,
+            otlp_endpoint,
+            sampling_ratio,
+            export_timeout,
+            max_queue_size,
+            metrics_enabled,
+            logs_enabled,
+            untraced_paths,
+            required_for_readiness,
+        }
+    }
+
+    /// Whether spans go to the OTLP collector
+    fn otlp_enabled(&self) -> bool {
+        self.exporters.contains(&TraceExporter::Otlp)
+    }
+
+    /// OTLP/HTTP URL for one signal (`traces`, `metrics`, `logs`)
+    ///
+    /// Unlike gRPC, the HTTP exporters post to the endpoint as-is, so add the signal path the
+    /// spec appends to `OTEL_EXPORTER_OTLP_ENDPOINT`.
+    fn otlp_http_endpoint(&self, signal: &str) -> String {
+        format!("{}/v1/{signal}", self.otlp_endpoint.trim_end_matches('/'))
+    }
+
+    /// Resource attributes identifying this service on every exported span
+    fn resource(&self) -> Resource {
+        Resource::new(vec![
+            KeyValue::new("service.name", self.service_name.clone()),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+            KeyValue::new("deployment.environment", self.environment.clone()),
+            KeyValue::new("service.instance.id", service_instance_id()),
+        ])
+    }
+
+    /// Bounded queue so a stalled collector drops spans instead of growing memory
+    fn batch_config(&self) -> BatchConfig {
+        BatchConfig::default()
+            .with_max_queue_size(self.max_queue_size)
+            .with_max_export_timeout(self.export_timeout)
+    }
+
+    /// Parent-based sampler so child spans follow the caller's sampling decision, skipping
+    /// requests to untraced paths such as probes and scrapes
+    fn sampler(&self) -> EndpointSampler {
+        EndpointSampler {
+            untraced_paths: self.untraced_paths.clone(),
+            inner: Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(self.sampling_ratio))),
+        }
+    }
+}
+
+/// Sampler dropping request spans whose `path` attribute is in a denylist
+///
+/// Only the `http_request` span carries `path`; spans below a dropped request see an
+/// unsampled parent, so the parent-based `inner` sampler drops them too.
+#[derive(Clone, Debug)]
+struct EndpointSampler {
+    untraced_paths: Vec<String>,
+    inner: Sampler,
+}
+
+impl ShouldSample for EndpointSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &OrderMap<Key, Value>,
+        links: &[Link],
+    ) -> SamplingResult {
+        let untraced = attributes
+            .get(&Key::from_static_str(PATH_ATTRIBUTE))
+            .is_some_and(|path| self.untraced_paths.iter().any(|p| *p == path.as_str()));
+
+        if untraced {
+            return SamplingResult {
+                decision: SamplingDecision::Drop,
+                attributes: Vec::new(),
+                trace_state: parent_context
+                    .map(|cx| cx.span().span_context().trace_state().clone())
+                    .unwrap_or_default(),
+            };
+        }
+
+        self.inner
+            .should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+    }
+}
+
+/// Identifier telling replicas apart: `HOSTNAME` (the pod name on Kubernetes), or a random
+/// UUID generated on first use when it is unset
+fn service_instance_id() -> &'static str {
+    SERVICE_INSTANCE_ID
+        .get_or_init(|| env_string("HOSTNAME").unwrap_or_else(|| Uuid::new_v4().to_string()))
+}
 
 /// Initialize all telemetry: OpenTelemetry, Tracing, and Loki
 pub async fn setup_telemetry() {
-    // Initialize Tracing Subscriber (must be first)
-    init_tracing();
+    // Route exporter errors (including spans dropped on a full queue) into our logs
+    // (under an `opentelemetry` target, so failed log exports aren't bridged back into the
+    // exporter that just failed)
+    let _ = global::set_error_handler(
+        |err| warn!(target: "opentelemetry.error", "OpenTelemetry error: {err}"),
+    );
+
+    let mut config_errors = Vec::new();
+    let config = TelemetryConfig::from_env(&mut config_errors);
+
+    // Cross-service propagation formats; by default W3C Trace Context (`traceparent`/
+    // `tracestate`) and W3C Baggage (`baggage`), with B3 available for Zipkin-era services.
+    // Extraction and injection both go through this composite.
+    global::set_text_map_propagator(TextMapCompositePropagator::new(
+        config
+            .propagators
+            .iter()
+            .map(|kind| kind.propagator())
+            .collect(),
+    ));
+
+    // Build the OpenTelemetry tracer first so its layer can join the subscriber
+    let (tracer, otel_error) = match init_opentelemetry(&config) {
+        Ok(tracer) => (Some(tracer), None),
+        Err(err) => (None, Some(err)),
+    };
 
-    // Initialize OpenTelemetry Tracer (Jaeger backend)
-    init_opentelemetry().await;
+    let metrics_result = config.metrics_enabled.then(|| init_otel_metrics(&config));
+
+    let (logger_provider, logs_error) = match config.logs_enabled.then(|| init_otel_logs(&config)) {
+        None => (None, None),
+        Some(Ok(provider)) => (Some(provider), None),
+        Some(Err(err)) => (None, Some(err)),
+    };
+
+    OTLP_REQUIRED.store(config.required_for_readiness, Ordering::Release);
+
+    // Initialize Tracing Subscriber (with the OTEL layers when available)
+    init_tracing(&config, tracer, logger_provider.as_ref());
+
+    for err in config_errors {
+        warn!("Ignoring invalid telemetry setting: {err}");
+    }
+
+    match otel_error {
+        None if config.otlp_enabled() => info!(
+            "✓ OpenTelemetry configured (exporters {}, propagators {}, OTLP {} export to {}, sampling ratio {}, instance {})",
+            list_names(&config.exporters),
+            list_names(&config.propagators),
+            config.otlp_protocol,
+            config.otlp_endpoint,
+            config.sampling_ratio,
+            service_instance_id()
+        ),
+        None => info!(
+            "✓ OpenTelemetry configured (exporters {}, propagators {}, sampling ratio {}, instance {})",
+            list_names(&config.exporters),
+            list_names(&config.propagators),
+            config.sampling_ratio,
+            service_instance_id()
+        ),
+        Some(err) => warn!("OpenTelemetry unavailable, serving without trace export: {err}"),
+    }
+
+    match metrics_result {
+        None => {}
+        Some(Ok(provider)) => {
+            metrics::init_otlp_instruments(&provider.meter(env!("CARGO_PKG_NAME")));
+            let _ = METER_PROVIDER.set(provider);
+            info!("✓ OTLP metrics export enabled");
+        }
+        Some(Err(err)) => warn!("OTLP metrics unavailable, serving without metrics export: {err}"),
+    }
+
+    if let Some(err) = logs_error {
+        warn!("OTLP logs unavailable, logging to stdout and file only: {err}");
+    } else if let Some(provider) = logger_provider {
+        *LOGGER_PROVIDER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(provider);
+        info!("✓ OTLP logs export enabled");
+    }
+
+    // Exporters connect lazily, so check the collector explicitly for the readiness probe.
+    // The probe runs in the background so a slow collector never delays startup; until it
+    // answers, readiness treats the collector as unreachable. Without OTLP export there is
+    // no collector to depend on.
+    if config.otlp_enabled() {
+        actix_web::rt::spawn(watch_collector(
+            config.otlp_endpoint.clone(),
+            config.required_for_readiness,
+        ));
+    } else {
+        OTLP_REACHABLE.store(true, Ordering::Release);
+    }
+
+    TELEMETRY_READY.store(true, Ordering::Release);
 
     info!("✓ Telemetry initialized successfully");
 }
 
-/// Initialize OpenTelemetry with Jaeger exporter
+/// Flush and shut down telemetry: pending spans go to the collector, buffered logs to disk
+pub async fn shutdown_telemetry() {
+    info!("Shutting down telemetry");
+
+    // This blocks until the batch processor drains, so run it off the runtime thread
+    // that drives the export.
+    let _ = tokio::task::spawn_blocking(global::shutdown_tracer_provider).await;
+
+    // Push the final metrics collection before exiting
+    if let Some(provider) = METER_PROVIDER.get().cloned()
+        && let Ok(Err(err)) = tokio::task::spawn_blocking(move || provider.shutdown()).await
+    {
+        warn!("Failed to flush OTLP metrics: {err}");
+    }
+
+    // Export the remaining log records; later events are no longer bridged
+    let logger_provider = LOGGER_PROVIDER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    // (`shutdown` panics while a logger still holds the provider, so flush and let the drop
+    // of the last reference shut the processors down)
+    if let Some(provider) = logger_provider {
+        let _ = tokio::task::spawn_blocking(move || provider.force_flush()).await;
+    }
+
+    // Dropping the guard flushes any log lines still queued for the file writer
+    drop(
+        FILE_GUARD
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take(),
+    );
+}
+
+/// Whether telemetry setup has completed
+pub fn is_telemetry_ready() -> bool {
+    TELEMETRY_READY.load(Ordering::Acquire)
+}
+
+/// Whether the OTLP collector answered the latest connectivity probe
+pub fn is_collector_reachable() -> bool {
+    OTLP_REACHABLE.load(Ordering::Acquire)
+}
+
+/// Whether readiness should fail while the collector is unreachable
+pub fn is_collector_required() -> bool {
+    OTLP_REQUIRED.load(Ordering::Acquire)
+}
+
+/// Check that a TCP connection to the collector endpoint can be opened
+///
+/// Cheaper than a test export and enough to tell "collector down" from "collector up"; any
+/// protocol-level problems still surface through the exporter's error handler.
+async fn probe_collector(endpoint: &str) -> bool {
+    let Ok(uri) = endpoint.parse::<Uri>() else {
+        return false;
+    };
+    let Some(host) = uri.host() else {
+        return false;
+    };
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        });
+
+    matches!(
+        tokio::time::timeout(OTLP_PROBE_TIMEOUT, TcpStream::connect((host, port))).await,
+        Ok(Ok(_))
+    )
+}
+
+/// Probe the collector, then keep probing while it is unreachable so readiness recovers
+/// once it comes up
+async fn watch_collector(endpoint: String, required: bool) {
+    if !probe_collector(&endpoint).await {
+        warn!(
+            required,
+            "OTLP collector unreachable at {endpoint}, readiness reports {}",
+            if required { "not ready" } else { "degraded" }
+        );
+
+        loop {
+            tokio::time::sleep(OTLP_REPROBE_INTERVAL).await;
+            if probe_collector(&endpoint).await {
+                break;
+            }
+        }
+    }
+
+    OTLP_REACHABLE.store(true, Ordering::Release);
+    info!("✓ OTLP collector reachable at {endpoint}");
+}
+
+/// Initialize OpenTelemetry with the configured span exporters
+///
+/// This creates a tracer that sends spans to an OTLP collector (Jaeger accepts OTLP gRPC on
+/// 4317 and HTTP/protobuf on 4318) through a batch span processor. The tracer provider is globally registered so
+/// `global::shutdown_tracer_provider()` flushes any pending spans on exit.
+///
+/// The gRPC channel connects lazily and export runs on the batch processor's background task,
+/// so a collector that is down at boot never blocks startup: failed exports surface through the
+/// global error handler and are retried with the next batch. Only a malformed endpoint fails
+/// here, in which case the app serves traffic without trace export.
+///
+/// Each exporter in `OTEL_EXPORTERS` gets its own span processor, so every sampled span reaches
+/// all of them. Stdout uses a simple processor so spans print as soon as they end.
+fn init_opentelemetry(config: &TelemetryConfig) -> Result<sdktrace::Tracer, TraceError> {
+    let provider = tracer_provider(config)?;
+    let tracer = provider.versioned_tracer(
+        env!("CARGO_PKG_NAME"),
+        Some(env!("CARGO_PKG_VERSION")),
+        None::<&'static str>,
+        None,
+    );
+    let _ = global::set_tracer_provider(provider);
+
+    Ok(tracer)
+}
+
+/// Tracer provider with one span processor per configured exporter
+fn tracer_provider(config: &TelemetryConfig) -> Result<sdktrace::TracerProvider, TraceError> {
+    build_tracer_provider(
+        config,
+        || span_exporter(config).build_span_exporter(),
+        io::stdout(),
+    )
+}
+
+/// `tracer_provider` with the OTLP exporter and stdout writer supplied by the caller; the
+/// OTLP exporter is only built when that exporter is configured
+fn build_tracer_provider<E: SpanExporter + 'static>(
+    config: &TelemetryConfig,
+    otlp_exporter: impl FnOnce() -> Result<E, TraceError>,
+    stdout: impl Write + Send + Sync + 'static,
+) -> Result<sdktrace::TracerProvider, TraceError> {
+    let mut builder = sdktrace::TracerProvider::builder().with_config(
+        sdktrace::config()
+            .with_sampler(config.sampler())
+            .with_resource(config.resource()),
+    );
+
+    if config.exporters.contains(&TraceExporter::Otlp) {
+        builder = builder.with_span_processor(
+            BatchSpanProcessor::builder(otlp_exporter()?, runtime::Tokio)
+                .with_batch_config(config.batch_config())
+                .build(),
+        );
+    }
+
+    if config.exporters.contains(&TraceExporter::Stdout) {
+        builder = builder.with_simple_exporter(
+            opentelemetry_stdout::SpanExporter::builder()
+                .with_writer(stdout)
+                .with_encoder(|writer, spans| {
+                    serde_json::to_writer_pretty(writer, &spans)
+                        .map_err(|err| TraceError::Other(Box::new(err)))
+                })
+                .build(),
+        );
+    }
+
+    Ok(builder.build())
+}
+
+/// Parse a comma-separated list such as `OTEL_EXPORTERS`, skipping unknown names and
+/// duplicates, and falling back to `default` when nothing valid remains
+fn env_list<T>(var: &'static str, default: &[T], errors: &mut Vec<ConfigError>) -> Vec<T>
+where
+    T: FromStr<Err = String> + PartialEq + Copy,
+{
+    let Some(raw) = env_string(var) else {
+        return default.to_vec();
+    };
+
+    let mut items = Vec::new();
+    for name in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        match name.parse() {
+            Ok(item) if !items.contains(&item) => items.push(item),
+            Ok(_) => {}
+            Err(reason) => errors.push(ConfigError {
+                var,
+                value: name.to_string(),
+                reason,
+            }),
+        }
+    }
+
+    if items.is_empty() {
+        return default.to_vec();
+    }
+    items
+}
+
+/// Comma-separated names for startup logs
+fn list_names<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Span exporter for the configured OTLP transport
+fn span_exporter(config: &TelemetryConfig) -> SpanExporterBuilder {
+    match config.otlp_protocol {
+        OtlpProtocol::Grpc => opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(&config.otlp_endpoint)
+            .with_timeout(config.export_timeout)
+            .into(),
+        OtlpProtocol::HttpProtobuf => opentelemetry_otlp::new_exporter()
+            .http()
+            .with_endpoint(config.otlp_http_endpoint("traces"))
+            .with_timeout(config.export_timeout)
+            .into(),
+    }
+}
+
+/// Metrics exporter for the configured OTLP transport
+fn metrics_exporter(config: &TelemetryConfig) -> MetricsExporterBuilder {
+    match config.otlp_protocol {
+        OtlpProtocol::Grpc => opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(&config.otlp_endpoint)
+            .with_timeout(config.export_timeout)
+            .into(),
+        OtlpProtocol::HttpProtobuf => opentelemetry_otlp::new_exporter()
+            .http()
+            .with_endpoint(config.otlp_http_endpoint("metrics"))
+            .with_timeout(config.export_timeout)
+            .into(),
+    }
+}
+
+/// Log exporter for the configured OTLP transport
+fn log_exporter(config: &TelemetryConfig) -> LogExporterBuilder {
+    match config.otlp_protocol {
+        OtlpProtocol::Grpc => opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(&config.otlp_endpoint)
+            .with_timeout(config.export_timeout)
+            .into(),
+        OtlpProtocol::HttpProtobuf => opentelemetry_otlp::new_exporter()
+            .http()
+            .with_endpoint(config.otlp_http_endpoint("logs"))
+            .with_timeout(config.export_timeout)
+            .into(),
+    }
+}
+
+/// Initialize an OTLP logger provider that batches log records to the collector
 ///
-/// This creates a tracer that sends spans to a Jaeger collector.
-/// The tracer is globally registered so it can be accessed anywhere in the application.
-async fn init_opentelemetry() {
-    // Note: Jaeger initialization happens via environment variables or config files
-    // JAEGER_AGENT_HOST, JAEGER_AGENT_PORT, JAEGER_SERVICE_NAME, etc.
-    // For now, we just log that OTEL is ready
-    info!("✓ OpenTelemetry configured (set JAEGER_* env vars to enable export)");
+/// The file layer stays the primary log path for Loki; this adds OTLP log records carrying
+/// the same resource attributes as the exported spans.
+fn init_otel_logs(config: &TelemetryConfig) -> Result<LoggerProvider, LogError> {
+    let exporter = log_exporter(config).build_log_exporter()?;
+
+    Ok(LoggerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_config(sdklogs::Config::default().with_resource(config.resource()))
+        .build())
+}
+
+/// Initialize an OTLP meter provider that periodically pushes the mirrored request metrics
+///
+/// Prometheus stays the primary metrics path; this only adds a push path for collectors.
+fn init_otel_metrics(config: &TelemetryConfig) -> Result<MeterProvider, MetricsError> {
+    opentelemetry_otlp::new_pipeline()
+        .metrics(runtime::Tokio)
+        .with_exporter(metrics_exporter(config))
+        .with_resource(config.resource())
+        .with_timeout(config.export_timeout)
+        .with_aggregation_selector(metrics::LatencyAggregationSelector)
+        .build()
 }
 
 /// Initialize tracing subscriber with multiple layers
@@ -45,39 +866,155 @@ async fn init_opentelemetry() {
 /// This sets up structured logging that can be:
 /// - Printed to stdout (fmt layer)
 /// - Sent to logs for Loki collection
-fn init_tracing() {
-    if let Err(error) = std::fs::create_dir_all("logs") {
-        eprintln!("Failed to create logs directory: {error}");
-    }
+/// - Exported as spans to an OTLP collector (OpenTelemetry layer)
+/// - Exported as log records to an OTLP collector (logs bridge layer)
+fn init_tracing(
+    config: &TelemetryConfig,
+    tracer: Option<sdktrace::Tracer>,
+    logger_provider: Option<&LoggerProvider>,
+) {
+    // Layer that writes JSON logs to a rolling file for Promtail scraping.
+    // If the file can't be opened we degrade to stdout-only logging.
+    let (file_layer, file_error) = match file_writer(config) {
+        Ok(file_writer) => {
+            let layer = tracing_subscriber::fmt::layer()
+                .with_writer(file_writer)
+                .with_ansi(false)
+                .json()
+                .map_event_format(|format| WithLogContext::new(format, config.log_trace_context));
+            (Some(layer), None)
+        }
+        Err(err) => (None, Some(err)),
+    };
 
-    let file_appender = tracing_appender::rolling::never("logs", "app.log");
-    let (file_writer, guard) = tracing_appender::non_blocking(file_appender);
-    let _ = FILE_GUARD.set(guard);
+    // Create a layer that formats logs to stdout (JSON by default, human-readable for local dev).
+    // `LOG_STDOUT=false` drops it, unless the file layer is unavailable too: never run with no sink.
+    let stdout_forced = !config.log_stdout && file_layer.is_none();
+    let stdout_layer = stdout_enabled(config.log_stdout, file_layer.is_some()).then(|| {
+        let layer = tracing_subscriber::fmt::layer().with_writer(std::io::stdout);
+        match config.log_format {
+            LogFormat::Json => layer
+                .json()
+                .map_event_format(|format| WithLogContext::new(format, config.log_trace_context))
+                .boxed(),
+            LogFormat::Pretty => layer.pretty().boxed(),
+            LogFormat::Compact => layer.compact().boxed(),
+        }
+    });
 
-    // Layer that writes JSON logs to a rolling file for Promtail scraping
-    let file_layer = tracing_subscriber::fmt::layer()
-        .with_writer(file_writer)
-        .with_ansi(false)
-        .json();
+    // Create environment filter (respects RUST_LOG env var), reloadable at runtime
+    let env_filter = reloadable_filter(
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    );
 
-    // Create a layer that formats logs to stdout with JSON
-    let stdout_layer = tracing_subscriber::fmt::layer()
-        .with_writer(std::io::stdout)
-        .json();
+    // Bridge tracing spans into OpenTelemetry spans
+    let otel_layer = tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
 
-    // Create environment filter (respects RUST_LOG env var)
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    // Bridge tracing events into OpenTelemetry log records
+    let logs_layer = logger_provider.map(logs_bridge);
 
-    // Combine layers (without OTEL initially to avoid trait bounds issues)
     tracing_subscriber::registry()
         .with(env_filter)
+        .with(RequestIdLayer)
         .with(stdout_layer)
         .with(file_layer)
+        .with(otel_layer)
+        .with(logs_layer)
         .init();
 
+    if let Some(err) = file_error {
+        warn!(
+            "File logging disabled, logging to stdout only ({}/{}): {err}",
+            config.log_dir, config.log_file
+        );
+    }
+
+    if stdout_forced {
+        warn!("LOG_STDOUT=false ignored because file logging is unavailable");
+    }
+
     info!("✓ Tracing subscriber initialized");
 }
 
+/// Layer exporting `tracing` events as log records through `provider`, except those from
+/// `UNBRIDGED_LOG_TARGETS`
+fn logs_bridge<S>(provider: &LoggerProvider) -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    OpenTelemetryTracingBridge::new(provider).with_filter(filter_fn(|metadata| {
+        !UNBRIDGED_LOG_TARGETS
+            .iter()
+            .any(|target| metadata.target().starts_with(target))
+    }))
+}
+
+/// Whether the stdout layer joins the registry: as configured, or forced on when the file layer
+/// is unavailable so logs always have at least one sink
+fn stdout_enabled(log_stdout: bool, file_available: bool) -> bool {
+    log_stdout || !file_available
+}
+
+/// Wrap `filter` so `set_log_filter` can replace it while the subscriber runs
+pub fn reloadable_filter(filter: EnvFilter) -> reload::Layer<EnvFilter, Registry> {
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = FILTER_HANDLE.set(handle);
+    filter
+}
+
+/// Replace the active log filter (same syntax as `RUST_LOG`, e.g. `debug` or `otel_tutorial=trace`)
+pub fn set_log_filter(directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(directives).map_err(|err| err.to_string())?;
+    let handle = FILTER_HANDLE
+        .get()
+        .ok_or_else(|| "tracing subscriber is not initialized".to_string())?;
+
+    handle.reload(filter).map_err(|err| err.to_string())
+}
+
+/// Open the log file as a non-blocking writer, keeping its flush guard alive globally
+fn file_writer(config: &TelemetryConfig) -> Result<NonBlocking, String> {
+    std::fs::create_dir_all(&config.log_dir)
+        .map_err(|err| format!("failed to create log directory: {err}"))?;
+
+    let file_appender = RollingFileAppender::builder()
+        .rotation(Rotation::NEVER)
+        .filename_prefix(&config.log_file)
+        .build(&config.log_dir)
+        .map_err(|err| format!("failed to open log file: {err}"))?;
+
+    let (file_writer, guard) = tracing_appender::non_blocking(DropCountingWriter(file_appender));
+    *FILE_GUARD.lock().unwrap_or_else(PoisonError::into_inner) = Some(guard);
+    let _ = FILE_DROP_COUNTER.set(file_writer.error_counter());
+
+    Ok(file_writer)
+}
+
+/// Lines dropped so far because the log file writer fell behind
+pub fn dropped_log_lines() -> usize {
+    FILE_DROP_COUNTER
+        .get()
+        .map_or(0, |counter| counter.dropped_lines())
+}
+
+/// Writer that counts failed writes (e.g. a full disk) in `log_lines_dropped_total`
+///
+/// The non-blocking worker only reports write errors on stderr; counting them here makes a
+/// log file that silently stopped growing visible on `/metrics`.
+struct DropCountingWriter<W>(W);
+
+impl<W: Write> Write for DropCountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write(buf)
+            .inspect_err(|_| metrics::track_log_line_dropped())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
 /// Helper to create a span for a specific operation
 ///
 /// Example usage:
@@ -95,3 +1032,496 @@ pub fn create_span(name: &str) -> tracing::Span {
         span_id = %uuid::Uuid::new_v4(),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        InMemorySpanExporter, TestTracing, attribute, lock_metrics_blocking, metric_value,
+    };
+    use actix_web::{
+        App,
+        http::header::{HeaderMap, HeaderName, HeaderValue},
+        test as actix_test, web,
+    };
+    use futures::future::BoxFuture;
+    use opentelemetry::{
+        logs::{AnyValue, LogResult, Severity},
+        trace::Tracer as _,
+    };
+    use opentelemetry_sdk::export::{
+        logs::{LogData, LogExporter},
+        trace::{ExportResult, SpanData, SpanExporter},
+    };
+
+    #[test]
+    fn create_span_is_exported() {
+        let tracing = TestTracing::install();
+
+        create_span("database_query").in_scope(|| info!("working"));
+
+        let spans = tracing.spans_named("operation");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(
+            attribute(&spans[0], "name").as_deref(),
+            Some("database_query")
+        );
+        assert!(spans[0].span_context.is_valid());
+    }
+
+    /// Defaults as `from_env` would produce them with no variables set, minus untraced paths
+    fn test_config() -> TelemetryConfig {
+        TelemetryConfig {
+            service_name: "test-service".to_string(),
+            environment: "test".to_string(),
+            log_dir: DEFAULT_LOG_DIR.to_string(),
+            log_file: DEFAULT_LOG_FILE.to_string(),
+            log_format: LogFormat::default(),
+            log_stdout: true,
+            log_trace_context: true,
+            exporters: vec![TraceExporter::Otlp],
+            propagators: vec![PropagatorKind::TraceContext, PropagatorKind::Baggage],
+            otlp_protocol: OtlpProtocol::default(),
+            otlp_endpoint: DEFAULT_OTLP_GRPC_ENDPOINT.to_string(),
+            sampling_ratio: 1.0,
+            export_timeout: Duration::from_millis(DEFAULT_OTLP_TIMEOUT_MS),
+            max_queue_size: DEFAULT_MAX_QUEUE_SIZE,
+            metrics_enabled: false,
+            logs_enabled: false,
+            untraced_paths: Vec::new(),
+            required_for_readiness: false,
+        }
+    }
+
+    /// Number of root spans exported when sampling at `ratio`
+    fn exported_with_ratio(ratio: f64) -> usize {
+        let config = TelemetryConfig {
+            sampling_ratio: ratio,
+            ..test_config()
+        };
+        let tracing = TestTracing::with_config(sdktrace::config().with_sampler(config.sampler()));
+
+        for _ in 0..20 {
+            tracing::info_span!("sampled_root")
+                .in_scope(|| tracing::info_span!("sampled_child").in_scope(|| {}));
+        }
+
+        let roots = tracing.spans_named("sampled_root").len();
+        let children = tracing.spans_named("sampled_child").len();
+        assert_eq!(roots, children, "children follow their parent's decision");
+        roots
+    }
+
+    #[test]
+    fn sampling_ratio_bounds() {
+        assert_eq!(exported_with_ratio(0.0), 0);
+        assert_eq!(exported_with_ratio(1.0), 20);
+    }
+
+    #[actix_web::test]
+    async fn health_checks_are_not_traced() {
+        let config = TelemetryConfig {
+            untraced_paths: vec!["/healthz".to_string()],
+            ..test_config()
+        };
+        let tracing = TestTracing::with_config(sdktrace::config().with_sampler(config.sampler()));
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(crate::custom_middleware::RequestIdMiddleware::new(
+                    actix_web::http::header::HeaderName::from_static("x-request-id"),
+                    Duration::from_secs(60),
+                ))
+                .route("/healthz", web::get().to(crate::handlers::liveness))
+                .route("/api/health", web::get().to(crate::handlers::liveness)),
+        )
+        .await;
+
+        for uri in ["/healthz", "/api/health"] {
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            actix_test::call_and_read_body(&app, req).await;
+        }
+
+        assert!(tracing.request_span("/healthz").is_none());
+        assert!(tracing.request_span("/api/health").is_some());
+        // Only the traced request's tree was exported
+        let spans = tracing.spans();
+        assert!(
+            spans
+                .iter()
+                .all(|span| span.span_context.trace_id() == spans[0].span_context.trace_id())
+        );
+    }
+
+    /// Exporter standing in for a stalled collector: exports never complete
+    #[derive(Debug)]
+    struct StalledExporter;
+
+    impl SpanExporter for StalledExporter {
+        fn export(&mut self, _batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            Box::pin(futures::future::pending())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shutdown_is_bounded_by_export_timeout() {
+        let config = TelemetryConfig {
+            export_timeout: Duration::from_millis(100),
+            ..test_config()
+        };
+        let provider = sdktrace::TracerProvider::builder()
+            .with_span_processor(
+                BatchSpanProcessor::builder(StalledExporter, runtime::Tokio)
+                    .with_batch_config(config.batch_config())
+                    .build(),
+            )
+            .build();
+
+        drop(provider.tracer("test").start("pending"));
+
+        // Dropping the last provider handle shuts the batch processor down, flushing the
+        // pending span into the stalled exporter
+        let shutdown = tokio::task::spawn_blocking(move || drop(provider));
+        tokio::time::timeout(Duration::from_secs(5), shutdown)
+            .await
+            .expect("shutdown waits for the export timeout, not the exporter")
+            .unwrap();
+    }
+
+    #[test]
+    fn resource_identifies_service() {
+        let resource = test_config().resource();
+        let value = |key| {
+            resource
+                .get(Key::from_static_str(key))
+                .map(|v| v.to_string())
+        };
+
+        assert_eq!(value("service.name").as_deref(), Some("test-service"));
+        assert_eq!(
+            value("service.version").as_deref(),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+        assert_eq!(value("deployment.environment").as_deref(), Some("test"));
+        assert_eq!(
+            value("service.instance.id").as_deref(),
+            Some(service_instance_id())
+        );
+    }
+
+    #[test]
+    fn instance_id_is_stable_across_resources() {
+        let instance_id = |resource: Resource| {
+            resource
+                .get(Key::from_static_str("service.instance.id"))
+                .map(|v| v.to_string())
+                .unwrap()
+        };
+
+        let first = instance_id(test_config().resource());
+        let second = instance_id(test_config().resource());
+
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn unusable_log_dir_is_an_error_not_a_panic() {
+        // A directory can't be created below a regular file
+        let file = std::env::temp_dir().join(format!("otel-tutorial-{}", Uuid::new_v4()));
+        std::fs::write(&file, b"").unwrap();
+        let config = TelemetryConfig {
+            log_dir: file.join("logs").to_string_lossy().into_owned(),
+            ..test_config()
+        };
+
+        let result = file_writer(&config);
+        std::fs::remove_file(&file).unwrap();
+
+        let err = result.expect_err("log dir below a file is rejected");
+        assert!(err.starts_with("failed to create log directory"), "{err}");
+    }
+
+    #[test]
+    fn log_format_parses_known_names_only() {
+        assert_eq!(LogFormat::default(), LogFormat::Json);
+        for (raw, format) in [
+            ("json", LogFormat::Json),
+            ("Pretty", LogFormat::Pretty),
+            ("COMPACT", LogFormat::Compact),
+        ] {
+            assert_eq!(raw.parse(), Ok(format), "{raw}");
+        }
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shutdown_flushes_pending_spans() {
+        let exporter = InMemorySpanExporter::default();
+        // Only this test installs a global provider; the others use their own
+        let _ = global::set_tracer_provider(
+            sdktrace::TracerProvider::builder()
+                .with_span_processor(
+                    BatchSpanProcessor::builder(exporter.clone(), runtime::Tokio)
+                        .with_batch_config(
+                            BatchConfig::default().with_scheduled_delay(Duration::from_secs(3600)),
+                        )
+                        .build(),
+                )
+                .build(),
+        );
+
+        drop(global::tracer("test").start("pending"));
+        assert!(exporter.spans().is_empty(), "still queued in the batch");
+
+        shutdown_telemetry().await;
+
+        let spans = exporter.spans();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "pending");
+    }
+
+    #[tokio::test]
+    async fn span_exporter_builds_for_each_protocol() {
+        for (protocol, endpoint) in [
+            (OtlpProtocol::Grpc, DEFAULT_OTLP_GRPC_ENDPOINT),
+            (OtlpProtocol::HttpProtobuf, DEFAULT_OTLP_HTTP_ENDPOINT),
+        ] {
+            let config = TelemetryConfig {
+                otlp_protocol: protocol,
+                otlp_endpoint: endpoint.to_string(),
+                ..test_config()
+            };
+
+            if let Err(err) = span_exporter(&config).build_span_exporter() {
+                panic!("{protocol} exporter: {err}");
+            }
+        }
+    }
+
+    #[test]
+    fn http_endpoint_gets_signal_path() {
+        let config = TelemetryConfig {
+            otlp_protocol: OtlpProtocol::HttpProtobuf,
+            otlp_endpoint: "http://collector:4318/".to_string(),
+            ..test_config()
+        };
+
+        assert_eq!(
+            config.otlp_http_endpoint("traces"),
+            "http://collector:4318/v1/traces"
+        );
+        assert_eq!(
+            "http/protobuf".parse::<OtlpProtocol>(),
+            Ok(OtlpProtocol::HttpProtobuf)
+        );
+        assert!("http/json".parse::<OtlpProtocol>().is_err());
+    }
+
+    #[test]
+    fn stdout_layer_composition() {
+        // (LOG_STDOUT, file layer available) -> stdout layer present
+        for (log_stdout, file_available, expected) in [
+            (true, true, true),
+            (true, false, true),
+            (false, true, false),
+            (false, false, true),
+        ] {
+            assert_eq!(
+                stdout_enabled(log_stdout, file_available),
+                expected,
+                "LOG_STDOUT={log_stdout} file={file_available}"
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn setup_completes_with_unreachable_collector() {
+        let config = TelemetryConfig {
+            // Nothing listens on port 1, so every export fails
+            otlp_endpoint: "http://127.0.0.1:1".to_string(),
+            export_timeout: Duration::from_millis(200),
+            ..test_config()
+        };
+
+        let provider = tracer_provider(&config).expect("the channel connects lazily");
+        drop(provider.tracer("test").start("undeliverable"));
+
+        // The failed export is bounded by the export timeout instead of hanging shutdown
+        let shutdown = tokio::task::spawn_blocking(move || drop(provider));
+        tokio::time::timeout(Duration::from_secs(5), shutdown)
+            .await
+            .expect("an unreachable collector does not block shutdown")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn collector_watch_marks_a_listening_collector_reachable() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+        // A reachable collector is recorded after the first probe, without re-probe delays
+        tokio::time::timeout(OTLP_PROBE_TIMEOUT, watch_collector(endpoint, false))
+            .await
+            .expect("the first probe answers");
+        assert!(is_collector_reachable());
+    }
+
+    /// Writer standing in for a full disk
+    struct FullDisk;
+
+    impl Write for FullDisk {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::StorageFull, "no space left"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_log_writes_are_counted() {
+        let _metrics = lock_metrics_blocking();
+        let dropped = || metric_value("log_lines_dropped_total", &[("reason", "write_error")]);
+        let before = dropped();
+
+        let mut writer = DropCountingWriter(FullDisk);
+        assert!(writer.write(b"lost line\n").is_err());
+        assert!(writer.write(b"another\n").is_err());
+
+        assert_eq!(dropped() - before, 2.0);
+    }
+
+    /// Writer standing in for stdout, shared with the test
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn spans_reach_every_configured_exporter() {
+        let config = TelemetryConfig {
+            exporters: vec![TraceExporter::Otlp, TraceExporter::Stdout],
+            ..test_config()
+        };
+        let otlp = InMemorySpanExporter::default();
+        let stdout = SharedBuffer::default();
+        let provider = build_tracer_provider(&config, || Ok(otlp.clone()), stdout.clone()).unwrap();
+
+        drop(provider.tracer("test").start("fanned_out"));
+        provider.force_flush();
+
+        assert_eq!(otlp.spans().len(), 1);
+        assert_eq!(otlp.spans()[0].name, "fanned_out");
+        let printed = String::from_utf8(stdout.0.lock().unwrap().clone()).unwrap();
+        assert!(printed.contains("fanned_out"), "{printed}");
+    }
+
+    #[test]
+    fn b3_headers_extract_to_a_valid_span_context() {
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let span_id = "00f067aa0ba902b7";
+        let single = [("b3", format!("{trace_id}-{span_id}-1"))];
+        let multi = [
+            ("x-b3-traceid", trace_id.to_string()),
+            ("x-b3-spanid", span_id.to_string()),
+            ("x-b3-sampled", "1".to_string()),
+        ];
+
+        for (kind, pairs) in [
+            (PropagatorKind::B3, &single[..]),
+            (PropagatorKind::B3Multi, &multi[..]),
+        ] {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(
+                    HeaderName::from_static(name),
+                    HeaderValue::from_str(value).unwrap(),
+                );
+            }
+
+            let context = kind
+                .propagator()
+                .extract(&crate::propagation::HeaderExtractor(&headers));
+            let span_context = context.span().span_context().clone();
+
+            assert!(span_context.is_valid(), "{kind}");
+            assert!(span_context.is_remote(), "{kind}");
+            assert!(span_context.is_sampled(), "{kind}");
+            assert_eq!(span_context.trace_id().to_string(), trace_id, "{kind}");
+            assert_eq!(span_context.span_id().to_string(), span_id, "{kind}");
+        }
+    }
+
+    /// Log exporter keeping every exported record in memory
+    #[derive(Clone, Debug, Default)]
+    struct InMemoryLogExporter {
+        records: std::sync::Arc<Mutex<Vec<LogData>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LogExporter for InMemoryLogExporter {
+        async fn export(&mut self, batch: Vec<LogData>) -> LogResult<()> {
+            self.records
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .extend(batch);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn events_are_exported_as_log_records() {
+        let exporter = InMemoryLogExporter::default();
+        let provider = LoggerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .with_config(sdklogs::Config::default().with_resource(test_config().resource()))
+            .build();
+        let subscriber = tracing_subscriber::registry().with(logs_bridge(&provider));
+
+        tracing::subscriber::with_default(subscriber, || {
+            warn!(user_id = 42, "Payment declined");
+            warn!(target: "opentelemetry.error", "OpenTelemetry error: export failed");
+        });
+        // Export runs on the processor's own thread; dropping the provider waits for it
+        drop(provider);
+
+        let records = exporter
+            .records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        assert_eq!(records.len(), 1, "exporter errors are not bridged");
+        let record = &records[0].record;
+        assert!(
+            matches!(&record.body, Some(AnyValue::String(body)) if body.as_str() == "Payment declined")
+        );
+        assert_eq!(record.severity_number, Some(Severity::Warn));
+        assert!(
+            record
+                .attributes
+                .iter()
+                .flatten()
+                .any(|(key, _)| key.as_str() == "user_id")
+        );
+        assert_eq!(
+            records[0]
+                .resource
+                .get(Key::from_static_str("service.name"))
+                .map(|v| v.to_string())
+                .as_deref(),
+            Some("test-service")
+        );
+    }
+}
@@ -0,0 +1,180 @@
+/// Trace context propagation helpers
+///
+/// This module shows how to:
+/// - Read W3C `traceparent`/`tracestate` and `baggage` headers (or B3, per `OTEL_PROPAGATORS`)
+///   from inbound requests
+/// - Continue the caller's trace instead of starting a new one
+/// - Read baggage entries (e.g. a tenant ID) set by an upstream service
+/// - Inject the active span's context and baggage into outgoing requests and responses
+/// - Look up the OpenTelemetry trace and span IDs behind a `tracing` span
+use actix_web::http::{
+    Method,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use opentelemetry::{
+    Context,
+    baggage::BaggageExt,
+    global,
+    propagation::{Extractor, Injector},
+    trace::TraceContextExt,
+};
+use std::collections::HashMap;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Adapts actix's `HeaderMap` to the OpenTelemetry `Extractor` interface
+pub struct HeaderExtractor<'a>(pub &'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Extract the remote parent context from inbound headers
+///
+/// Returns `None` when neither a valid trace context nor any baggage is present. Baggage
+/// without a `traceparent` still yields a context, so it reaches handlers under a new trace.
+pub fn extract_context(headers: &HeaderMap) -> Option<Context> {
+    let context =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+
+    let is_useful = context.span().span_context().is_valid() || !context.baggage().is_empty();
+    is_useful.then_some(context)
+}
+
+/// Baggage entries propagated with the current request, keyed by name
+///
+/// Example usage inside a handler:
+/// ```
+/// let tenant = current_baggage().get("tenant.id").cloned();
+/// ```
+pub fn current_baggage() -> HashMap<String, String> {
+    tracing::Span::current()
+        .context()
+        .baggage()
+        .iter()
+        .map(|(key, (value, _metadata))| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Hex trace ID of the OpenTelemetry span backing `span`, if it is being traced
+pub fn trace_id(span: &tracing::Span) -> Option<String> {
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+/// Hex span ID of the OpenTelemetry span backing `span`, if it is being traced
+pub fn span_id(span: &tracing::Span) -> Option<String> {
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+
+    span_context
+        .is_valid()
+        .then(|| span_context.span_id().to_string())
+}
+
+/// Link `span` to the OpenTelemetry span backing `target`, returning whether a link was added
+///
+/// A link relates spans without nesting them, e.g. work fanned out into its own trace. Nothing
+/// is linked when `target` isn't traced, since a link to an invalid context carries no data.
+pub fn add_link(span: &tracing::Span, target: &tracing::Span) -> bool {
+    let context = target.context();
+    let span_context = context.span().span_context().clone();
+
+    if !span_context.is_valid() {
+        return false;
+    }
+
+    span.add_link(span_context);
+    true
+}
+
+/// Adapts actix's `HeaderMap` to the OpenTelemetry `Injector` interface
+pub struct HeaderInjector<'a>(pub &'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+/// Serialize the active span's context (`traceparent`) and baggage into `headers`
+pub fn inject_context(headers: &mut HeaderMap) {
+    let context = tracing::Span::current().context();
+
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
+
+/// Build an outbound request that continues the current trace and forwards its baggage
+///
+/// Example usage:
+/// ```
+/// let client = awc::Client::default();
+/// let response = traced_request(&client, Method::GET, "http://localhost:8081/api/health")
+///     .send()
+///     .await?;
+/// ```
+#[allow(dead_code)]
+pub fn traced_request(client: &awc::Client, method: Method, url: &str) -> awc::ClientRequest {
+    let mut request = client.request(method, url);
+    inject_context(request.headers_mut());
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestTracing;
+
+    #[test]
+    fn span_context_round_trips_through_headers() {
+        let _tracing = TestTracing::install();
+        let span = tracing::info_span!("outbound");
+        let _entered = span.enter();
+
+        let mut headers = HeaderMap::new();
+        inject_context(&mut headers);
+        let extracted = extract_context(&headers).unwrap();
+        let extracted = extracted.span().span_context().clone();
+
+        let context = span.context();
+        let original = context.span().span_context().clone();
+        assert!(original.is_valid());
+        assert_eq!(extracted.trace_id(), original.trace_id());
+        assert_eq!(extracted.span_id(), original.span_id());
+        assert_eq!(extracted.trace_flags(), original.trace_flags());
+        assert!(extracted.is_remote());
+    }
+
+    #[actix_web::test]
+    async fn traced_request_carries_current_context() {
+        let _tracing = TestTracing::install();
+        let span = tracing::info_span!("outbound");
+        let _entered = span.enter();
+
+        let client = awc::Client::default();
+        let request = traced_request(&client, Method::GET, "http://localhost:8081/api/health");
+        let extracted = extract_context(request.headers()).unwrap();
+        let extracted = extracted.span().span_context().clone();
+
+        let context = span.context();
+        let original = context.span().span_context().clone();
+        assert_eq!(extracted.trace_id(), original.trace_id());
+        assert_eq!(extracted.span_id(), original.span_id());
+    }
+}
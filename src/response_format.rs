@@ -0,0 +1,77 @@
+/// Response body format negotiation
+///
+/// User endpoints answer with MessagePack when the `Accept` header asks for
+/// `application/msgpack` and JSON otherwise. Handlers take a `ResponseFormat` argument and
+/// pass it to their response helper; error bodies stay JSON either way.
+use actix_web::{FromRequest, HttpRequest, dev::Payload, http::header::ACCEPT};
+use serde::Serialize;
+use std::{
+    convert::Infallible,
+    future::{Ready, ready},
+};
+
+/// MessagePack media types; `x-msgpack` is the older unregistered spelling
+const MSGPACK_MEDIA_TYPES: [&str; 2] = ["application/msgpack", "application/x-msgpack"];
+
+/// Encoding negotiated from the request's `Accept` header
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl ResponseFormat {
+    /// Pick MessagePack only when the client lists it explicitly
+    fn negotiate(accept: &str) -> Self {
+        let wants_msgpack = accept.split(',').any(|range| {
+            let media_type = range.split(';').next().unwrap_or_default().trim();
+            MSGPACK_MEDIA_TYPES
+                .iter()
+                .any(|msgpack| media_type.eq_ignore_ascii_case(msgpack))
+        });
+
+        if wants_msgpack {
+            Self::MessagePack
+        } else {
+            Self::Json
+        }
+    }
+
+    /// Short name recorded as the `format` span field
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::MessagePack => "msgpack",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// Serialize `value`; MessagePack keeps field names so bodies decode into the same structs
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Json => serde_json::to_vec(value).map_err(|err| err.to_string()),
+            Self::MessagePack => rmp_serde::to_vec_named(value).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+impl FromRequest for ResponseFormat {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .headers()
+            .get(ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .map(Self::negotiate)
+            .unwrap_or_default()))
+    }
+}
@@ -0,0 +1,78 @@
+/// In-memory user storage
+///
+/// Stands in for a real database so the handlers have persistent state to trace.
+/// The store is shared across all worker threads via `web::Data`.
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use uuid::Uuid;
+
+use crate::handlers::User;
+
+/// Thread-safe map of user ID to user record
+#[derive(Default)]
+pub struct UserStore {
+    users: Mutex<HashMap<String, User>>,
+}
+
+impl UserStore {
+    /// Create a store pre-populated with a couple of demo users
+    pub fn with_seed_users() -> Self {
+        let store = Self::default();
+
+        for (name, email) in [("Alice", "alice@example.com"), ("Bob", "bob@example.com")] {
+            store.insert(User {
+                id: Uuid::new_v4().to_string(),
+                name: name.to_string(),
+                email: email.to_string(),
+            });
+        }
+
+        store
+    }
+
+    /// Insert or replace a user, returning the store size afterwards
+    pub fn insert(&self, user: User) -> usize {
+        let mut users = self.lock();
+        users.insert(user.id.clone(), user);
+        users.len()
+    }
+
+    /// Look up a user by ID
+    pub fn get(&self, id: &str) -> Option<User> {
+        self.lock().get(id).cloned()
+    }
+
+    /// Replace an existing user's name and email, returning the updated record
+    pub fn update(&self, id: &str, name: String, email: String) -> Option<User> {
+        let mut users = self.lock();
+        let user = users.get_mut(id)?;
+        user.name = name;
+        user.email = email;
+        Some(user.clone())
+    }
+
+    /// Remove a user by ID, returning the removed record if it existed
+    pub fn remove(&self, id: &str) -> Option<User> {
+        self.lock().remove(id)
+    }
+
+    /// All users, ordered by name (then ID) so listings are stable
+    pub fn list(&self) -> Vec<User> {
+        let mut users: Vec<User> = self.lock().values().cloned().collect();
+        users.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        users
+    }
+
+    /// Number of stored users
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    // A panic while holding the lock cannot leave the map half-updated, so recover from poisoning
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, User>> {
+        self.users.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,167 @@
+/// Shared helpers for unit tests
+///
+/// Spans are exported to memory through the same `tracing-opentelemetry` bridge the app uses,
+/// so tests can assert on what a collector would receive.
+use std::sync::{Arc, Mutex, Once, PoisonError};
+
+use futures::future::BoxFuture;
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_sdk::{
+    export::trace::{ExportResult, SpanData, SpanExporter},
+    propagation::{BaggagePropagator, TextMapCompositePropagator, TraceContextPropagator},
+    trace::{Config, TracerProvider},
+};
+use tracing::subscriber::DefaultGuard;
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::log_context::RequestIdLayer;
+
+/// Span exporter keeping every exported span in memory
+#[derive(Clone, Debug, Default)]
+pub struct InMemorySpanExporter {
+    spans: Arc<Mutex<Vec<SpanData>>>,
+}
+
+impl InMemorySpanExporter {
+    pub fn spans(&self) -> Vec<SpanData> {
+        self.spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl SpanExporter for InMemorySpanExporter {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        self.spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(batch);
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+/// Install the default propagators (`tracecontext,baggage`) once for the whole test binary
+fn install_propagators() {
+    static PROPAGATORS: Once = Once::new();
+
+    PROPAGATORS.call_once(|| {
+        global::set_text_map_propagator(TextMapCompositePropagator::new(vec![
+            Box::new(TraceContextPropagator::new()),
+            Box::new(BaggagePropagator::new()),
+        ]));
+    });
+}
+
+/// Subscriber exporting spans to memory, installed for the current thread until dropped
+pub struct TestTracing {
+    provider: TracerProvider,
+    exporter: InMemorySpanExporter,
+    _guard: DefaultGuard,
+}
+
+impl TestTracing {
+    pub fn install() -> Self {
+        Self::with_config(Config::default())
+    }
+
+    /// Like `install`, with a custom provider config (e.g. a sampler or resource under test)
+    pub fn with_config(config: Config) -> Self {
+        install_propagators();
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_config(config)
+            .with_simple_exporter(exporter.clone())
+            .build();
+
+        let subscriber = tracing_subscriber::registry()
+            .with(RequestIdLayer)
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        Self {
+            provider,
+            exporter,
+            _guard: tracing::subscriber::set_default(subscriber),
+        }
+    }
+
+    /// Every span ended so far, in the order they finished
+    pub fn spans(&self) -> Vec<SpanData> {
+        // The simple processor exports on a background thread; wait for it to catch up
+        self.provider.force_flush();
+        self.exporter.spans()
+    }
+
+    /// Finished `http_request` span for `path`; exported under its `otel.name`, so matched by
+    /// the `path` attribute
+    pub fn request_span(&self, path: &str) -> Option<SpanData> {
+        self.spans()
+            .into_iter()
+            .find(|span| attribute(span, "path").as_deref() == Some(path))
+    }
+
+    /// Finished spans with the given name
+    pub fn spans_named(&self, name: &str) -> Vec<SpanData> {
+        self.spans()
+            .into_iter()
+            .filter(|span| span.name == name)
+            .collect()
+    }
+}
+
+/// String value of a span attribute
+pub fn attribute(span: &SpanData, key: &'static str) -> Option<String> {
+    span.attributes
+        .get(&opentelemetry::Key::from_static_str(key))
+        .map(|value| value.as_str().into_owned())
+}
+
+/// String value of an attribute on a span event
+pub fn event_attribute(event: &opentelemetry::trace::Event, key: &str) -> Option<String> {
+    event
+        .attributes
+        .iter()
+        .find(|attribute| attribute.key.as_str() == key)
+        .map(|attribute| attribute.value.as_str().into_owned())
+}
+
+/// Serializes tests asserting on the global Prometheus registry, which `/metrics/reset` zeroes
+static METRICS: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Hold the registry for an async test
+pub async fn lock_metrics() -> tokio::sync::MutexGuard<'static, ()> {
+    METRICS.lock().await
+}
+
+/// Hold the registry for a synchronous test
+pub fn lock_metrics_blocking() -> tokio::sync::MutexGuard<'static, ()> {
+    METRICS.blocking_lock()
+}
+
+/// Current value of a counter or gauge series in the default registry, or the sample count of
+/// a histogram series (0 when absent)
+pub fn metric_value(name: &str, labels: &[(&str, &str)]) -> f64 {
+    prometheus::gather()
+        .iter()
+        .filter(|family| family.get_name() == name)
+        .flat_map(|family| family.get_metric())
+        .filter(|metric| {
+            labels.iter().all(|(key, value)| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|pair| pair.get_name() == *key && pair.get_value() == *value)
+            })
+        })
+        .map(|metric| {
+            if metric.has_gauge() {
+                metric.get_gauge().get_value()
+            } else if metric.has_histogram() {
+                metric.get_histogram().get_sample_count() as f64
+            } else {
+                metric.get_counter().get_value()
+            }
+        })
+        .sum()
+}